@@ -2,22 +2,21 @@ mod pan_camera;
 
 use bevy::{
     diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
-    ecs::system::SystemParam,
     prelude::*,
 };
-use jostle::{Agent, JostlePlugin, Layer, TileMap, Velocity};
+use jostle::{
+    Agent, JostleDebug, JostleDebugPlugin, JostlePlugin, Layer, LayerGrid, TileGrid, Velocity,
+};
 
 use crate::pan_camera::{PanCamera, PanCameraPlugin};
 
-#[derive(SystemParam)]
-struct TileMapParam;
-
 fn main() {
     App::new()
         .add_plugins((
             DefaultPlugins,
             PanCameraPlugin,
-            JostlePlugin::<TileMapParam>::default(),
+            JostlePlugin::<LayerGrid>::default(),
+            JostleDebugPlugin::<LayerGrid>::default(),
         ))
         .add_plugins((
             FrameTimeDiagnosticsPlugin::default(),
@@ -53,9 +52,20 @@ fn startup(
         Mesh2d(meshes.add(Rectangle::new(20.0, 20.0))),
     ));
 
-    // Spawn layer
+    // Spawn layer, surrounded by walls
+    let mut grid = TileGrid::new();
+    grid.fill_rect(IVec2::new(-11, -11), IVec2::new(10, -11), true);
+    grid.fill_rect(IVec2::new(-11, 10), IVec2::new(10, 10), true);
+    grid.fill_rect(IVec2::new(-11, -10), IVec2::new(-11, 9), true);
+    grid.fill_rect(IVec2::new(10, -10), IVec2::new(10, 9), true);
+
     let layer_id = commands
-        .spawn((Layer::default(), Visibility::default()))
+        .spawn((
+            Layer::default(),
+            grid,
+            JostleDebug::default(),
+            Visibility::default(),
+        ))
         .id();
 
     // Spawn agents
@@ -105,9 +115,3 @@ fn update(mut agents: Query<&mut Velocity, With<Agent>>) {
         }
     });
 }
-
-impl TileMap for TileMapParam {
-    fn is_solid(&self, _: Entity, tile: IVec2) -> bool {
-        tile.x < -10 || tile.x >= 10 || tile.y < -10 || tile.y >= 10
-    }
-}
@@ -16,25 +16,53 @@ use crate::{
 #[require(Transform, AgentState, Velocity, InterpolationState)]
 pub struct Agent {
     radius: f32,
+    groups: CollisionGroups,
+}
+
+/// Determines which agents and walls an [`Agent`] collides with.
+///
+/// Two agents collide if each is a member of a group in the other's filter. An agent collides with walls if its
+/// filter contains [`CollisionGroups::WALLS`]. The [`CollisionGroups::WALLS`] group is reserved, and is ignored when
+/// checking for collisions between agents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "scene", derive(serde::Serialize, serde::Deserialize))]
+pub struct CollisionGroups {
+    /// The groups this agent is a member of.
+    pub memberships: u32,
+    /// The groups this agent collides with.
+    pub filters: u32,
 }
 
 /// The velocity of an [`Agent`], in units per second.
 #[derive(Component, Clone, Copy, Debug, Default)]
 pub struct Velocity(pub Vec2);
 
+/// The mass of an [`Agent`], which determines how far it is pushed by other agents.
+///
+/// When two agents with a mass collide, both continue along the contact normal with their combined momentum, so
+/// heavier agents push lighter ones aside while keeping most of their own velocity. Agents without a mass never push
+/// or get pushed, and collisions involving them stop both agents.
+///
+/// The mass must be positive. Agents whose mass is not positive are treated as having no mass.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Mass(pub f32);
+
 #[derive(Component, Clone, Copy, Debug, Default)]
 #[component(on_replace = AgentState::on_replace)]
 pub(crate) struct AgentState {
     pub(crate) position: Vec2,
     pub(crate) velocity: Vec2,
     pub(crate) tile: Option<Tile>,
+    pub(crate) reach: i32,
 }
 
+#[allow(clippy::type_complexity)]
 pub(crate) fn update_tile(
     layers: Query<&Layer>,
     mut agents: Query<
         (
             Entity,
+            &Agent,
             &Transform,
             &mut AgentState,
             &Velocity,
@@ -48,15 +76,30 @@ pub(crate) fn update_tile(
 
     agents
         .par_iter_mut()
-        .for_each(|(id, transform, mut position, velocity, parent)| {
+        .for_each(|(id, agent, transform, mut position, velocity, parent)| {
             position.position = transform.translation.xy();
             position.velocity = velocity.0;
 
-            let tile = parent.and_then(|parent| {
-                let layer = layers.get(parent.get()).ok()?;
-                Some(Tile::floor(parent.get(), position.position, layer.scale()))
+            let layer =
+                parent.and_then(|parent| Some((parent.get(), layers.get(parent.get()).ok()?)));
+            let tile = layer.map(|(id, layer)| Tile::floor(id, position.position, layer.scale()));
+            let reach = layer.map_or(position.reach, |(_, layer)| {
+                Tile::reach(agent.radius(), layer.scale())
             });
 
+            if position.reach != reach {
+                // The agent's radius or layer changed size, so re-index it from scratch.
+                if let Some(old) = position.tile.take() {
+                    writer.lock().unwrap().write(TileChanged {
+                        agent: id,
+                        old: Some(old),
+                        new: None,
+                        reach: position.reach,
+                    });
+                }
+                position.reach = reach;
+            }
+
             if position.tile != tile {
                 let old = position.tile;
                 position.tile = tile;
@@ -65,29 +108,95 @@ pub(crate) fn update_tile(
                     agent: id,
                     old,
                     new: tile,
+                    reach,
                 });
             }
         });
 }
 
+impl Mass {
+    /// Creates a new [`Mass`], which must be positive.
+    pub fn new(mass: f32) -> Self {
+        debug_assert!(mass > 0.0, "mass must be positive");
+        Mass(mass)
+    }
+
+    // Returns the mass if it is positive and finite.
+    pub(crate) fn get(&self) -> Option<f32> {
+        (self.0 > 0.0 && self.0.is_finite()).then_some(self.0)
+    }
+}
+
 impl Agent {
     pub fn new(radius: f32) -> Self {
-        Agent { radius }
+        Agent {
+            radius,
+            groups: CollisionGroups::default(),
+        }
+    }
+
+    /// Sets the collision groups of this agent.
+    pub fn with_collision_groups(mut self, groups: CollisionGroups) -> Self {
+        self.groups = groups;
+        self
     }
 
     pub fn radius(&self) -> f32 {
         self.radius
     }
+
+    /// Returns the collision groups of this agent.
+    pub fn collision_groups(&self) -> CollisionGroups {
+        self.groups
+    }
+}
+
+impl CollisionGroups {
+    /// The group containing all walls of the tile map.
+    pub const WALLS: u32 = 1 << 31;
+    /// All groups.
+    pub const ALL: u32 = u32::MAX;
+    /// No groups.
+    pub const NONE: u32 = 0;
+
+    /// Creates a new [`CollisionGroups`] with the given memberships and filters.
+    pub const fn new(memberships: u32, filters: u32) -> Self {
+        CollisionGroups {
+            memberships,
+            filters,
+        }
+    }
+
+    /// Returns `true` if agents with these groups collide with agents with the `other` groups.
+    pub fn collides_with(&self, other: CollisionGroups) -> bool {
+        let memberships = self.memberships & !CollisionGroups::WALLS;
+        let other_memberships = other.memberships & !CollisionGroups::WALLS;
+        memberships & other.filters != 0 && other_memberships & self.filters != 0
+    }
+
+    /// Returns `true` if agents with these groups collide with walls.
+    pub fn collides_with_walls(&self) -> bool {
+        self.filters & CollisionGroups::WALLS != 0
+    }
+}
+
+/// By default, agents are members of all groups, and collide with all agents and walls.
+impl Default for CollisionGroups {
+    fn default() -> Self {
+        CollisionGroups::new(CollisionGroups::ALL, CollisionGroups::ALL)
+    }
 }
 
 impl AgentState {
     fn on_replace(mut world: DeferredWorld, context: HookContext) {
         let position = world.entity(context.entity).get::<AgentState>().unwrap();
         if let Some(tile) = position.tile {
+            let reach = position.reach;
             world.write_message(TileChanged {
                 agent: context.entity,
                 old: Some(tile),
                 new: None,
+                reach,
             });
         }
     }
@@ -106,6 +215,39 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn collision_groups_default() {
+        let groups = CollisionGroups::default();
+        assert!(groups.collides_with(groups));
+        assert!(groups.collides_with_walls());
+    }
+
+    #[test]
+    fn collision_groups_one_sided() {
+        let ghost = CollisionGroups::new(0b01, CollisionGroups::WALLS);
+        let unit = CollisionGroups::default();
+        assert!(!ghost.collides_with(unit));
+        assert!(!unit.collides_with(ghost));
+        assert!(ghost.collides_with_walls());
+    }
+
+    #[test]
+    fn collision_groups_teams() {
+        let team_a = CollisionGroups::new(0b01, !0b01);
+        let team_b = CollisionGroups::new(0b10, !0b10);
+        assert!(!team_a.collides_with(team_a));
+        assert!(!team_b.collides_with(team_b));
+        assert!(team_a.collides_with(team_b));
+        assert!(team_b.collides_with(team_a));
+    }
+
+    #[test]
+    fn collision_groups_no_walls() {
+        let flying = CollisionGroups::new(CollisionGroups::ALL, !CollisionGroups::WALLS);
+        assert!(flying.collides_with(CollisionGroups::default()));
+        assert!(!flying.collides_with_walls());
+    }
+
     #[test]
     fn agent_spawned() {
         let mut app = make_app();
@@ -131,6 +273,7 @@ mod tests {
                 agent,
                 old: None,
                 new: Some(Tile::new(layer, 1, 2)),
+                reach: 1,
             }]
         );
         assert_eq!(index.get(Tile::new(layer, 1, 2)), &[agent]);
@@ -214,6 +357,7 @@ mod tests {
                 agent,
                 old: Some(Tile::new(layer, 1, 2)),
                 new: Some(Tile::new(layer, 2, 1)),
+                reach: 1,
             }]
         );
         assert_eq!(index.get(Tile::new(layer, 0, 1)), &[]);
@@ -261,12 +405,55 @@ mod tests {
                 agent,
                 old: Some(Tile::new(layer1, 1, 2)),
                 new: Some(Tile::new(layer2, 1, 2)),
+                reach: 1,
             }]
         );
         assert_eq!(index.get(Tile::new(layer1, 1, 2)), &[]);
         assert_eq!(index.get(Tile::new(layer2, 1, 2)), &[agent]);
     }
 
+    #[test]
+    fn agent_radius_changed() {
+        let mut app = make_app();
+        let layer = app.world_mut().spawn(Layer::default()).id();
+        let agent = app
+            .world_mut()
+            .spawn((
+                Agent::new(0.5),
+                Transform::from_translation(Vec3::new(1.0, 2.6, 0.0)),
+                ChildOf(layer),
+            ))
+            .id();
+        app.update();
+
+        app.world_mut().entity_mut(agent).insert(Agent::new(1.5));
+
+        let changes = update_get_changes(&mut app);
+        let (state, index) = get_state(&mut app, agent);
+
+        assert_eq!(state.tile, Some(Tile::new(layer, 1, 2)));
+        assert_eq!(state.reach, 3);
+        assert_eq!(
+            changes,
+            vec![
+                TileChanged {
+                    agent,
+                    old: Some(Tile::new(layer, 1, 2)),
+                    new: None,
+                    reach: 1,
+                },
+                TileChanged {
+                    agent,
+                    old: None,
+                    new: Some(Tile::new(layer, 1, 2)),
+                    reach: 3,
+                }
+            ]
+        );
+        assert_eq!(index.get(Tile::new(layer, 4, 5)), &[agent]);
+        assert_eq!(index.get(Tile::new(layer, 5, 5)), &[]);
+    }
+
     #[test]
     fn agent_layer_removed() {
         let mut app = make_app();
@@ -295,6 +482,7 @@ mod tests {
                 agent,
                 old: Some(Tile::new(layer, 1, 2)),
                 new: None,
+                reach: 1,
             }]
         );
         assert_eq!(index.get(Tile::new(layer, 1, 2)), &[]);
@@ -325,6 +513,7 @@ mod tests {
                 agent,
                 old: Some(Tile::new(layer, 1, 2)),
                 new: None,
+                reach: 1,
             }]
         );
         assert_eq!(index.get(Tile::new(layer, 1, 2)), &[]);
@@ -355,6 +544,7 @@ mod tests {
                 agent,
                 old: Some(Tile::new(layer, 1, 2)),
                 new: None,
+                reach: 1,
             }]
         );
         assert_eq!(index.get(Tile::new(layer, 1, 2)), &[]);
@@ -0,0 +1,433 @@
+//! Runs a [`JostleScene`] headlessly for a fixed number of steps, writing metrics for each step as CSV or JSON.
+//!
+//! ```text
+//! jostle-sim <SCENE> [--steps <N>] [--timestep <SECONDS>] [--format csv|json] [--output <PATH>]
+//! ```
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    process::ExitCode,
+    time::Duration,
+};
+
+use bevy::{
+    diagnostic::{DiagnosticPath, DiagnosticsStore},
+    prelude::*,
+    time::{TimePlugin, TimeUpdateStrategy},
+};
+use jostle::{
+    Agent, AgentCollided, JostlePlugin, JostleScene, JostleSystems, LayerGrid, Velocity, diagnostic,
+};
+use serde_json::{Map, Value, json};
+
+const USAGE: &str = "usage: jostle-sim <SCENE> [--steps <N>] [--timestep <SECONDS>] \
+    [--format csv|json] [--output <PATH>]";
+
+// Agents closer than the sum of their radii by less than this are touching rather than overlapping.
+const OVERLAP_TOLERANCE: f32 = 1e-4;
+
+#[derive(Debug)]
+struct Args {
+    scene: String,
+    steps: u32,
+    timestep: f64,
+    format: Format,
+    output: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+// The metrics of the most recent simulation step.
+#[derive(Resource, Default)]
+struct StepMetrics(Option<Metrics>);
+
+struct Metrics {
+    step: u32,
+    time: f32,
+    agents: usize,
+    collisions: usize,
+    overlaps: usize,
+    mean_speed: f32,
+    // The time taken by each system, in milliseconds, in the order of `diagnostic::PATHS`.
+    timings: Vec<Option<f64>>,
+    // The counts recorded by the simulation, in the order of `diagnostic::COUNTS`.
+    counts: Vec<Option<f64>>,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut scene = None;
+    let mut parsed = Args {
+        scene: String::new(),
+        steps: 100,
+        timestep: 1.0 / 64.0,
+        format: Format::Csv,
+        output: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for {name}"))
+        };
+        match arg.as_str() {
+            "--steps" => {
+                parsed.steps = value("--steps")?
+                    .parse()
+                    .map_err(|err| format!("invalid --steps: {err}"))?;
+            }
+            "--timestep" => {
+                parsed.timestep = value("--timestep")?
+                    .parse()
+                    .map_err(|err| format!("invalid --timestep: {err}"))?;
+                if !parsed.timestep.is_finite() || parsed.timestep <= 0.0 {
+                    return Err("--timestep must be positive".to_owned());
+                }
+            }
+            "--format" => {
+                parsed.format = match value("--format")?.as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    format => return Err(format!("unknown format: {format}")),
+                };
+            }
+            "--output" => parsed.output = Some(value("--output")?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {arg}")),
+            _ if scene.is_none() => scene = Some(arg),
+            _ => return Err(format!("unexpected argument: {arg}")),
+        }
+    }
+
+    parsed.scene = scene.ok_or("missing scene path")?;
+    Ok(parsed)
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let scene = JostleScene::from_ron(&fs::read_to_string(&args.scene)?)?;
+
+    let mut app = make_app(Duration::from_secs_f64(args.timestep));
+    scene.spawn(app.world_mut())?;
+
+    let mut rows = Vec::new();
+    while rows.len() < args.steps as usize {
+        app.update();
+        if let Some(mut metrics) = app.world_mut().resource_mut::<StepMetrics>().0.take() {
+            let store = app.world().resource::<DiagnosticsStore>();
+            let values = |paths: &[DiagnosticPath]| {
+                paths
+                    .iter()
+                    .map(|path| store.get(path).and_then(|diagnostic| diagnostic.value()))
+                    .collect()
+            };
+            metrics.timings = values(&diagnostic::PATHS);
+            metrics.counts = values(&diagnostic::COUNTS);
+            rows.push(metrics);
+        }
+    }
+
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    match args.format {
+        Format::Csv => write_csv(&mut output, &rows)?,
+        Format::Json => write_json(&mut output, &rows)?,
+    }
+    output.flush()?;
+    Ok(())
+}
+
+fn make_app(timestep: Duration) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        TransformPlugin,
+        TimePlugin,
+        JostlePlugin::<LayerGrid>::default(),
+    ));
+    app.finish();
+    app.cleanup();
+
+    app.insert_resource(Time::<Fixed>::from_duration(timestep));
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+    app.init_resource::<StepMetrics>();
+    app.add_systems(FixedPostUpdate, record.after(JostleSystems));
+
+    app
+}
+
+fn record(
+    mut reader: MessageReader<AgentCollided>,
+    agents: Query<(&Agent, &Transform, &Velocity, &ChildOf)>,
+    time: Res<Time>,
+    mut metrics: ResMut<StepMetrics>,
+    mut step: Local<u32>,
+) {
+    *step += 1;
+
+    let mut circles: Vec<_> = agents
+        .iter()
+        .map(|(agent, transform, _, parent)| (parent.0, transform.translation.xy(), agent.radius()))
+        .collect();
+    let speed: f32 = agents
+        .iter()
+        .map(|(_, _, velocity, _)| velocity.0.length())
+        .sum();
+
+    metrics.0 = Some(Metrics {
+        step: *step,
+        time: time.elapsed_secs(),
+        agents: circles.len(),
+        collisions: reader.read().count(),
+        overlaps: count_overlaps(&mut circles),
+        mean_speed: if circles.is_empty() {
+            0.0
+        } else {
+            speed / circles.len() as f32
+        },
+        timings: Vec::new(),
+        counts: Vec::new(),
+    });
+}
+
+// Counts the pairs of agents in the same layer which overlap, by sweeping across them in order of their left edge.
+fn count_overlaps(circles: &mut [(Entity, Vec2, f32)]) -> usize {
+    circles.sort_by(|a, b| {
+        a.0.index()
+            .cmp(&b.0.index())
+            .then((a.1.x - a.2).total_cmp(&(b.1.x - b.2)))
+    });
+
+    let mut overlaps = 0;
+    for (i, &(layer, position, radius)) in circles.iter().enumerate() {
+        for &(other_layer, other_position, other_radius) in &circles[i + 1..] {
+            if other_layer != layer || other_position.x - other_radius > position.x + radius {
+                break;
+            }
+            if position.distance(other_position) < radius + other_radius - OVERLAP_TOLERANCE {
+                overlaps += 1;
+            }
+        }
+    }
+    overlaps
+}
+
+fn write_csv(output: &mut impl Write, rows: &[Metrics]) -> io::Result<()> {
+    write!(output, "step,time,agents,collisions,overlaps,mean_speed")?;
+    for path in diagnostic::PATHS.iter().chain(&diagnostic::COUNTS) {
+        write!(output, ",{}", path.as_str())?;
+    }
+    writeln!(output)?;
+
+    for row in rows {
+        write!(
+            output,
+            "{},{},{},{},{},{}",
+            row.step, row.time, row.agents, row.collisions, row.overlaps, row.mean_speed
+        )?;
+        for value in row.timings.iter().chain(&row.counts) {
+            match value {
+                Some(value) => write!(output, ",{value}")?,
+                None => write!(output, ",")?,
+            }
+        }
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+fn write_json(output: &mut impl Write, rows: &[Metrics]) -> io::Result<()> {
+    let rows: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            let values = |paths: &[DiagnosticPath], values: &[Option<f64>]| {
+                paths
+                    .iter()
+                    .zip(values)
+                    .map(|(path, &value)| (path.as_str().to_owned(), json!(value)))
+                    .collect::<Map<String, Value>>()
+            };
+            json!({
+                "step": row.step,
+                "time": row.time,
+                "agents": row.agents,
+                "collisions": row.collisions,
+                "overlaps": row.overlaps,
+                "mean_speed": row.mean_speed,
+                "timings": values(&diagnostic::PATHS, &row.timings),
+                "counts": values(&diagnostic::COUNTS, &row.counts),
+            })
+        })
+        .collect();
+    serde_json::to_writer_pretty(&mut *output, &rows)?;
+    writeln!(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_args_defaults() {
+        let args = parse(&["scene.ron"]).unwrap();
+
+        assert_eq!(args.scene, "scene.ron");
+        assert_eq!(args.steps, 100);
+        assert_eq!(args.timestep, 1.0 / 64.0);
+        assert_eq!(args.format, Format::Csv);
+        assert_eq!(args.output, None);
+    }
+
+    #[test]
+    fn parse_args_options() {
+        let args = parse(&[
+            "--steps",
+            "5",
+            "scene.ron",
+            "--timestep",
+            "0.5",
+            "--format",
+            "json",
+            "--output",
+            "out.json",
+        ])
+        .unwrap();
+
+        assert_eq!(args.scene, "scene.ron");
+        assert_eq!(args.steps, 5);
+        assert_eq!(args.timestep, 0.5);
+        assert_eq!(args.format, Format::Json);
+        assert_eq!(args.output.as_deref(), Some("out.json"));
+    }
+
+    #[test]
+    fn parse_args_invalid() {
+        assert_eq!(parse(&[]).unwrap_err(), "missing scene path");
+        assert_eq!(
+            parse(&["a.ron", "b.ron"]).unwrap_err(),
+            "unexpected argument: b.ron"
+        );
+        assert_eq!(
+            parse(&["a.ron", "--speed"]).unwrap_err(),
+            "unknown option: --speed"
+        );
+        assert_eq!(
+            parse(&["a.ron", "--steps"]).unwrap_err(),
+            "missing value for --steps"
+        );
+        assert!(parse(&["a.ron", "--steps", "-1"]).is_err());
+        assert_eq!(
+            parse(&["a.ron", "--timestep", "0"]).unwrap_err(),
+            "--timestep must be positive"
+        );
+        assert_eq!(
+            parse(&["a.ron", "--timestep", "inf"]).unwrap_err(),
+            "--timestep must be positive"
+        );
+        assert_eq!(
+            parse(&["a.ron", "--format", "xml"]).unwrap_err(),
+            "unknown format: xml"
+        );
+    }
+
+    #[test]
+    fn count_overlaps_in_layer() {
+        let (first, second) = (
+            Entity::from_raw_u32(1).unwrap(),
+            Entity::from_raw_u32(2).unwrap(),
+        );
+        let mut circles = vec![
+            (first, Vec2::new(0.0, 0.0), 0.5),
+            (first, Vec2::new(0.8, 0.0), 0.5),
+            // Touching the second agent.
+            (first, Vec2::new(1.8, 0.0), 0.5),
+            // Overlapping the first agent, but far from it along the x axis.
+            (first, Vec2::new(0.0, 0.9), 0.5),
+            // Overlapping the first agent in a different layer.
+            (second, Vec2::new(0.0, 0.0), 0.5),
+        ];
+
+        assert_eq!(count_overlaps(&mut circles), 2);
+    }
+
+    #[test]
+    fn write_csv_rows() {
+        let mut output = Vec::new();
+        write_csv(&mut output, &[metrics()]).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        let header: Vec<_> = lines[0].split(',').collect();
+        let row: Vec<_> = lines[1].split(',').collect();
+        assert_eq!(
+            header.len(),
+            6 + diagnostic::PATHS.len() + diagnostic::COUNTS.len()
+        );
+        assert_eq!(row.len(), header.len());
+        assert_eq!(row[..6], ["3", "0.5", "2", "1", "0", "1.5"]);
+        assert_eq!(row[6], "0.25");
+        assert_eq!(row[7], "");
+        let count = header
+            .iter()
+            .position(|&column| column == diagnostic::COUNTS[0].as_str());
+        assert_eq!(row[count.unwrap()], "2");
+    }
+
+    #[test]
+    fn write_json_rows() {
+        let mut output = Vec::new();
+        write_json(&mut output, &[metrics()]).unwrap();
+        let rows: Value = serde_json::from_slice(&output).unwrap();
+
+        let row = &rows[0];
+        assert_eq!(row["step"], 3);
+        assert_eq!(row["agents"], 2);
+        assert_eq!(row["mean_speed"], 1.5);
+        assert_eq!(row["timings"][diagnostic::PATHS[0].as_str()], 0.25);
+        assert_eq!(row["timings"][diagnostic::PATHS[1].as_str()], Value::Null);
+        assert_eq!(row["counts"][diagnostic::COUNTS[0].as_str()], 2.0);
+    }
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|&arg| arg.to_owned()))
+    }
+
+    fn metrics() -> Metrics {
+        let mut timings = vec![None; diagnostic::PATHS.len()];
+        timings[0] = Some(0.25);
+        let mut counts = vec![None; diagnostic::COUNTS.len()];
+        counts[0] = Some(2.0);
+        Metrics {
+            step: 3,
+            time: 0.5,
+            agents: 2,
+            collisions: 1,
+            overlaps: 0,
+            mean_speed: 1.5,
+            timings,
+            counts,
+        }
+    }
+}
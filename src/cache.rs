@@ -0,0 +1,373 @@
+use bevy::{
+    ecs::system::{StaticSystemParam, SystemParamItem},
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+    utils::Parallel,
+};
+use smallvec::SmallVec;
+
+use crate::{
+    Agent, Layer,
+    agent::AgentState,
+    tile::{EDGES, Tile, TileMap, TileRegion, TileShape},
+    validate::TileMapChanged,
+};
+
+/// A component which caches the walls around each tile of a [`Layer`], so collisions in it are found without calling
+/// into the [`TileMap`].
+///
+/// Tiles around each agent are cached in square chunks before collisions are processed each step, and kept until
+/// invalidated or until no agent has been near them for a while, so the cache only grows with the area around agents.
+/// Tiles which aren't cached, for example those far from any agent, are read from the [`TileMap`] as normal.
+///
+/// Tiles in a [`TileMapChanged`] message are invalidated automatically. Tiles may also be invalidated directly with
+/// [`TileCache::invalidate`] or [`TileCache::clear`].
+#[derive(Component, Clone, Debug, Default)]
+pub struct TileCache {
+    chunks: HashMap<IVec2, Chunk>,
+}
+
+// Tiles are cached, invalidated and evicted in square chunks of this size.
+const CHUNK_SIZE: i32 = 32;
+
+// A square of tiles whose walls are all cached, in row-major order.
+#[derive(Clone, Debug)]
+struct Chunk(Box<[Boundaries; (CHUNK_SIZE * CHUNK_SIZE) as usize]>);
+
+// The number of steps between evictions.
+const EVICTION_INTERVAL: u32 = 64;
+
+// The shape of a tile, and which of its edges and corners are walls.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Boundaries {
+    bits: u8,
+    shape: TileShape,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update<T>(
+    mut reader: MessageReader<TileMapChanged>,
+    mut caches: Query<(Entity, &mut TileCache)>,
+    agents: Query<(&Agent, &AgentState)>,
+    layers: Query<&Layer>,
+    time: Res<Time>,
+    map: StaticSystemParam<T>,
+    mut missing: Local<Parallel<Vec<(Entity, IVec2)>>>,
+    mut used: Local<Parallel<Vec<(Entity, IVec2)>>>,
+    mut used_chunks: Local<HashSet<(Entity, IVec2)>>,
+    mut steps: Local<u32>,
+) where
+    T: TileMap,
+    for<'w, 's> SystemParamItem<'w, 's, T>: TileMap,
+{
+    let evict = steps.is_multiple_of(EVICTION_INTERVAL);
+    *steps = steps.wrapping_add(1);
+
+    for event in reader.read() {
+        if let Ok((_, mut cache)) = caches.get_mut(event.layer) {
+            for &tile in &event.tiles {
+                cache.invalidate(tile);
+            }
+        }
+    }
+
+    agents.par_iter().for_each(|(agent, state)| {
+        let Some(tile) = state.tile else {
+            return;
+        };
+        if !agent.collision_groups().collides_with_walls() {
+            return;
+        }
+
+        let Ok((_, cache)) = caches.get(tile.layer()) else {
+            return;
+        };
+        let Ok(layer) = layers.get(tile.layer()) else {
+            return;
+        };
+
+        // Cache the tiles the agent may reach this step, with a margin in case it is deflected, and those bordering
+        // them.
+        let end = state.position + state.velocity * time.delta_secs();
+        let margin = agent.radius() + layer.tile_size();
+        let (min, max) = (
+            Tile::floor(
+                tile.layer(),
+                state.position.min(end) - margin,
+                layer.scale(),
+            )
+            .tile()
+                - IVec2::ONE,
+            Tile::floor(
+                tile.layer(),
+                state.position.max(end) + margin,
+                layer.scale(),
+            )
+            .tile()
+                + IVec2::ONE,
+        );
+
+        for chunk in Tile::rect(tile.layer(), chunk(min), chunk(max)) {
+            if !cache.chunks.contains_key(&chunk.tile()) {
+                missing
+                    .borrow_local_mut()
+                    .push((tile.layer(), chunk.tile()));
+            }
+            if evict {
+                used.borrow_local_mut().push((tile.layer(), chunk.tile()));
+            }
+        }
+    });
+
+    // Agents near each other need the same chunks, which are only filled for the first.
+    for (layer, chunk) in missing.drain() {
+        if let Ok((_, mut cache)) = caches.get_mut(layer)
+            && !cache.chunks.contains_key(&chunk)
+        {
+            cache.fill_chunk(&*map, layer, chunk);
+        }
+    }
+
+    if evict {
+        used_chunks.clear();
+        used_chunks.extend(used.drain());
+
+        for (layer, mut cache) in &mut caches {
+            cache.evict(|chunk| used_chunks.contains(&(layer, chunk)));
+        }
+    }
+}
+
+impl TileCache {
+    /// Creates a new, empty [`TileCache`].
+    pub fn new() -> Self {
+        TileCache::default()
+    }
+
+    /// Removes any cached walls which depend on the tile at the given coordinates, or the edges on its sides, along
+    /// with the rest of the chunks containing them.
+    ///
+    /// This must be called, or a [`TileMapChanged`] message written, whenever a tile or edge changes.
+    pub fn invalidate(&mut self, tile: IVec2) {
+        for chunk in Tile::rect(Entity::PLACEHOLDER, chunk(tile - 1), chunk(tile + 1)) {
+            self.chunks.remove(&chunk.tile());
+        }
+    }
+
+    /// Removes all cached walls.
+    ///
+    /// This is an alternative to invalidating each tile when most of the layer changes at once, such as when a new
+    /// level is loaded into it.
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
+
+    // Removes the cached chunks which aren't used.
+    fn evict(&mut self, is_used: impl Fn(IVec2) -> bool) {
+        self.chunks.retain(|&chunk, _| is_used(chunk));
+    }
+
+    // Returns the walls of each tile in the rectangle between `min` and `max`, inclusive, in row-major order, if they
+    // are all cached. Each chunk the rectangle overlaps is looked up once, and its rows are copied a slice at a time.
+    pub(crate) fn get_rect(&self, min: IVec2, max: IVec2) -> Option<SmallVec<[Boundaries; 16]>> {
+        let width = (max.x - min.x + 1) as usize;
+        let mut boundaries =
+            SmallVec::from_elem(Boundaries::default(), width * (max.y - min.y + 1) as usize);
+        for chunk_position in Tile::rect(Entity::PLACEHOLDER, chunk(min), chunk(max)) {
+            let chunk = self.chunks.get(&chunk_position.tile())?;
+            let origin = chunk_position.tile() * CHUNK_SIZE;
+            let start = (min - origin).max(IVec2::ZERO);
+            let end = (max - origin).min(IVec2::splat(CHUNK_SIZE - 1));
+            let len = (end.x - start.x + 1) as usize;
+            for y in start.y..=end.y {
+                let from = (y * CHUNK_SIZE + start.x) as usize;
+                let to =
+                    (origin.y + y - min.y) as usize * width + (origin.x + start.x - min.x) as usize;
+                boundaries[to..to + len].copy_from_slice(&chunk.0[from..from + len]);
+            }
+        }
+        Some(boundaries)
+    }
+
+    // Caches every chunk overlapping the rectangle between `min` and `max`, inclusive, which isn't already cached.
+    #[cfg(test)]
+    fn fill(&mut self, map: &impl TileMap, layer: Entity, min: IVec2, max: IVec2) {
+        for chunk in Tile::rect(layer, chunk(min), chunk(max)) {
+            if !self.chunks.contains_key(&chunk.tile()) {
+                self.fill_chunk(map, layer, chunk.tile());
+            }
+        }
+    }
+
+    fn fill_chunk<M: TileMap>(&mut self, map: &M, layer: Entity, chunk: IVec2) {
+        let origin = chunk * CHUNK_SIZE;
+        let region = TileRegion::new(map, None, layer, origin, origin + CHUNK_SIZE - 1);
+        let mut boundaries = Box::new([Boundaries::default(); (CHUNK_SIZE * CHUNK_SIZE) as usize]);
+        for (i, tile) in Tile::rect(layer, origin, origin + CHUNK_SIZE - 1).enumerate() {
+            boundaries[i] = Boundaries::new(&region, tile.tile());
+        }
+        self.chunks.insert(chunk, Chunk(boundaries));
+    }
+}
+
+// Returns the chunk containing a tile.
+fn chunk(tile: IVec2) -> IVec2 {
+    tile.div_euclid(IVec2::splat(CHUNK_SIZE))
+}
+
+impl Boundaries {
+    const CORNER: u8 = 1 << 4;
+
+    // Computes the walls of a tile, which must be within the region.
+    fn new(region: &TileRegion, tile: IVec2) -> Self {
+        let mut bits = 0;
+        for edge in 0..EDGES.len() {
+            if region.is_edge(tile, edge) {
+                bits |= Boundaries::edge_bit(edge);
+            }
+        }
+        if region.is_convex_corner(tile) {
+            bits |= Boundaries::CORNER;
+        }
+
+        Boundaries {
+            bits,
+            shape: region.shape(tile),
+        }
+    }
+
+    fn edge_bit(edge: usize) -> u8 {
+        1 << edge
+    }
+
+    pub(crate) fn shape(self) -> TileShape {
+        self.shape
+    }
+
+    // Returns `true` if there is a wall on the given edge, as an index into `EDGES`.
+    pub(crate) fn is_edge(self, edge: usize) -> bool {
+        self.bits & Boundaries::edge_bit(edge) != 0
+    }
+
+    // Returns `true` if the bottom-left corner of the tile is a convex corner of solid tiles.
+    pub(crate) fn is_corner(self) -> bool {
+        self.bits & Boundaries::CORNER != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::SystemParam, prelude::*};
+
+    use super::*;
+
+    #[derive(SystemParam)]
+    struct Room;
+
+    impl TileMap for Room {
+        fn is_solid(&self, _: Entity, tile: IVec2) -> bool {
+            tile.x <= 0
+                || tile.y <= 0
+                || tile.x >= 5
+                || tile.y >= 4
+                || tile == IVec2::new(3, 2)
+                || tile == IVec2::new(1, 1)
+                || tile == IVec2::new(4, 3)
+        }
+
+        fn shape(&self, _: Entity, tile: IVec2) -> TileShape {
+            match (tile.x, tile.y) {
+                (1, 1) => TileShape::HalfSouth,
+                (4, 3) => TileShape::DiagonalNorthEast,
+                _ => TileShape::Full,
+            }
+        }
+    }
+
+    #[test]
+    fn cached_region_matches_map() {
+        let mut cache = TileCache::new();
+        cache.fill(
+            &Room,
+            Entity::PLACEHOLDER,
+            IVec2::splat(-2),
+            IVec2::splat(7),
+        );
+
+        for min in Tile::rect(Entity::PLACEHOLDER, IVec2::splat(-1), IVec2::splat(3)) {
+            let (min, max) = (min.tile(), min.tile() + IVec2::new(2, 1));
+            let cached = TileRegion::new(&Room, Some(&cache), Entity::PLACEHOLDER, min, max);
+            let uncached = TileRegion::new(&Room, None, Entity::PLACEHOLDER, min, max);
+            assert!(cached.is_cached());
+
+            assert_eq!(
+                cached.walls().collect::<Vec<_>>(),
+                uncached.walls().collect::<Vec<_>>(),
+                "{min}"
+            );
+            for tile in Tile::rect(Entity::PLACEHOLDER, min, max) {
+                assert_eq!(cached.is_solid(tile.tile()), uncached.is_solid(tile.tile()));
+                assert_eq!(
+                    cached.boundaries(tile.tile()).collect::<Vec<_>>(),
+                    uncached.boundaries(tile.tile()).collect::<Vec<_>>(),
+                    "{tile:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn uncached_region() {
+        let mut cache = TileCache::new();
+        cache.fill(&Room, Entity::PLACEHOLDER, IVec2::ZERO, IVec2::splat(2));
+
+        let region = TileRegion::new(
+            &Room,
+            Some(&cache),
+            Entity::PLACEHOLDER,
+            IVec2::new(0, 1),
+            IVec2::splat(2),
+        );
+        assert!(!region.is_cached());
+    }
+
+    #[test]
+    fn evict() {
+        let mut cache = TileCache::new();
+        cache.fill(
+            &Room,
+            Entity::PLACEHOLDER,
+            IVec2::splat(-CHUNK_SIZE),
+            IVec2::splat(CHUNK_SIZE),
+        );
+        cache.evict(|chunk| chunk == IVec2::ZERO);
+
+        assert!(
+            cache
+                .get_rect(IVec2::ZERO, IVec2::splat(CHUNK_SIZE - 1))
+                .is_some()
+        );
+        assert_eq!(cache.chunks.keys().collect::<Vec<_>>(), vec![&IVec2::ZERO]);
+    }
+
+    #[test]
+    fn invalidate() {
+        let mut cache = TileCache::new();
+        cache.fill(
+            &Room,
+            Entity::PLACEHOLDER,
+            IVec2::splat(-CHUNK_SIZE),
+            IVec2::splat(CHUNK_SIZE),
+        );
+        cache.invalidate(IVec2::new(CHUNK_SIZE - 1, 2));
+
+        for chunk in Tile::rect(Entity::PLACEHOLDER, IVec2::splat(-1), IVec2::ONE) {
+            let dependent = chunk.tile() == IVec2::ZERO || chunk.tile() == IVec2::X;
+            assert_eq!(
+                cache.chunks.contains_key(&chunk.tile()),
+                !dependent,
+                "{chunk:?}"
+            );
+        }
+    }
+}
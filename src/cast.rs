@@ -0,0 +1,249 @@
+use bevy::{
+    ecs::system::{StaticSystemParam, SystemParam, SystemParamItem},
+    prelude::*,
+};
+
+use crate::{
+    Agent, CollisionGroups, CollisionTarget, Layer,
+    agent::AgentState,
+    cache::TileCache,
+    collision::{agent_collision, edge_collision, face_collision, normal_vector},
+    obstacle::{Obstacle, ObstacleIndex, ObstacleState},
+    sensor::Sensor,
+    tile::{Tile, TileIndex, TileMap, TileRegion, Wall},
+};
+
+/// A system parameter for casting rays and circles through a [`Layer`], to find the first solid tile, agent or
+/// obstacle in their path.
+///
+/// Like [`AgentQuery`](crate::AgentQuery), agents are found at their positions in the most recent simulation step.
+/// [`Sensor`](crate::Sensor)s are never hit.
+#[derive(SystemParam)]
+pub struct CastQuery<'w, 's, T>
+where
+    T: TileMap + 'static,
+{
+    index: Res<'w, TileIndex>,
+    obstacle_index: Res<'w, ObstacleIndex>,
+    layers: Query<'w, 's, &'static Layer>,
+    caches: Query<'w, 's, &'static TileCache>,
+    agents: Query<'w, 's, (&'static Agent, &'static AgentState, Has<Sensor>)>,
+    obstacles: Query<'w, 's, (&'static Obstacle, &'static ObstacleState)>,
+    map: StaticSystemParam<'w, 's, T>,
+}
+
+/// The first object hit by a cast from [`CastQuery`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CastHit {
+    /// The agent, obstacle or wall which was hit.
+    pub target: CollisionTarget,
+    /// The distance travelled along the cast's direction before the hit.
+    pub distance: f32,
+    /// The position of the cast's center at the time of the hit.
+    pub point: Vec2,
+    /// The normal of the hit surface, pointing back towards the cast.
+    pub normal: Vec2,
+}
+
+impl<T> CastQuery<'_, '_, T>
+where
+    T: TileMap,
+    for<'w, 's> SystemParamItem<'w, 's, T>: TileMap,
+{
+    /// Casts a ray from `origin` in the given direction, returning the first hit within `max_distance`.
+    ///
+    /// Returns `None` if `max_distance` is negative or not finite.
+    ///
+    /// Only agents whose collision groups would collide with `groups` are hit, and walls and obstacles are only hit if
+    /// `groups` collides with walls. A ray starting inside the solid part of a tile hits it immediately.
+    pub fn cast_ray(
+        &self,
+        layer: Entity,
+        origin: Vec2,
+        direction: Dir2,
+        max_distance: f32,
+        groups: CollisionGroups,
+    ) -> Option<CastHit> {
+        self.cast_circle(layer, origin, 0.0, direction, max_distance, groups)
+    }
+
+    /// Casts a circle with the given radius from `origin` in the given direction, returning the first hit within
+    /// `max_distance`.
+    ///
+    /// Returns `None` if `max_distance` is negative or not finite.
+    /// Only agents whose collision groups would collide with `groups` are hit, and walls and obstacles are only hit if
+    /// `groups` collides with walls. A circle whose center starts inside the solid part of a tile hits it immediately, and one
+    /// which starts overlapping an agent only hits it if moving towards the agent's center.
+    pub fn cast_circle(
+        &self,
+        layer_id: Entity,
+        origin: Vec2,
+        radius: f32,
+        direction: Dir2,
+        max_distance: f32,
+        groups: CollisionGroups,
+    ) -> Option<CastHit> {
+        if !max_distance.is_finite() || max_distance < 0.0 {
+            return None;
+        }
+
+        let layer = self.layers.get(layer_id).ok()?;
+        let direction = direction.as_vec2();
+        let tile_size = layer.tile_size();
+        let map = &*self.map;
+        let cache = self.caches.get(layer_id).ok();
+
+        // Casts starting inside the solid part of a tile, which may be only a half or triangle, are blocked at once.
+        let origin_tile = Tile::floor(layer_id, origin, layer.scale()).tile();
+        if groups.collides_with_walls()
+            && map.is_solid(layer_id, origin_tile)
+            && map
+                .shape(layer_id, origin_tile)
+                .overlaps_circle(origin * layer.scale() - origin_tile.as_vec2(), 0.0)
+        {
+            return Some(CastHit {
+                target: CollisionTarget::Wall,
+                distance: 0.0,
+                point: origin,
+                normal: -direction,
+            });
+        }
+
+        // The number of tiles around the center of the cast which it may touch.
+        let reach = (radius * layer.scale()).ceil() as i32;
+        let mut cast = Cast {
+            origin,
+            direction,
+            max_distance,
+            nearest: None,
+        };
+
+        for (tile, entered) in Tile::traverse(layer_id, layer, origin, direction, max_distance) {
+            if cast
+                .nearest
+                .is_some_and(|nearest| entered > nearest.distance)
+            {
+                break;
+            }
+
+            let (min, max) = (
+                tile.tile() - IVec2::splat(reach),
+                tile.tile() + IVec2::splat(reach),
+            );
+
+            let agents = self.index.query(layer_id, min, max, |id| {
+                let (agent, state, is_sensor) = self.agents.get(id).ok()?;
+                Some(((agent, state, is_sensor), state.tile?.tile()))
+            });
+            for (target, (agent, state, is_sensor)) in agents {
+                if is_sensor || !groups.collides_with(agent.collision_groups()) {
+                    continue;
+                }
+
+                if let Some(t) =
+                    agent_collision(state.position - origin, -direction, radius + agent.radius())
+                {
+                    cast.hit(CollisionTarget::Agent(target), t, |point| {
+                        (point - state.position).normalize_or_zero()
+                    });
+                }
+            }
+
+            if !groups.collides_with_walls() {
+                continue;
+            }
+
+            for (target, obstacle, state) in
+                self.obstacle_index
+                    .query(&self.obstacles, layer_id, min, max)
+            {
+                if let Some(t) = agent_collision(
+                    state.position - origin,
+                    -direction,
+                    radius + obstacle.radius(),
+                ) {
+                    cast.hit(CollisionTarget::Obstacle(target), t, |point| {
+                        (point - state.position).normalize_or_zero()
+                    });
+                }
+            }
+
+            for wall in TileRegion::new(map, cache, layer_id, min, max).walls() {
+                match wall {
+                    Wall::Edge(wall_tile, wall_normal) => {
+                        if let Some(t) = edge_collision(
+                            origin,
+                            direction,
+                            radius,
+                            wall_tile,
+                            wall_normal,
+                            tile_size,
+                        ) {
+                            cast.hit(CollisionTarget::Wall, t, |_| normal_vector(wall_normal));
+                        }
+                    }
+                    // A ray can only hit a corner where it also hits an edge.
+                    Wall::Corner(_) if radius == 0.0 => {}
+                    Wall::Corner(corner) => {
+                        let corner = corner.as_vec2() * tile_size;
+                        if let Some(t) = agent_collision(corner - origin, -direction, radius) {
+                            cast.hit(CollisionTarget::Wall, t, |point| {
+                                (point - corner).normalize_or_zero()
+                            });
+                        }
+                    }
+                    Wall::Face(start, end, wall_normal) => {
+                        if let Some(t) = face_collision(
+                            origin,
+                            direction,
+                            radius,
+                            start * tile_size,
+                            end * tile_size,
+                            wall_normal,
+                        ) {
+                            cast.hit(CollisionTarget::Wall, t, |_| wall_normal);
+                        }
+                    }
+                    Wall::Vertex(_) if radius == 0.0 => {}
+                    Wall::Vertex(vertex) => {
+                        let vertex = vertex * tile_size;
+                        if let Some(t) = agent_collision(vertex - origin, -direction, radius) {
+                            cast.hit(CollisionTarget::Wall, t, |point| {
+                                (point - vertex).normalize_or_zero()
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        cast.nearest
+    }
+}
+
+struct Cast {
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+    nearest: Option<CastHit>,
+}
+
+impl Cast {
+    fn hit(&mut self, target: CollisionTarget, distance: f32, normal: impl FnOnce(Vec2) -> Vec2) {
+        // Casts which start overlapping an object hit it immediately.
+        let distance = distance.max(0.0);
+        if distance <= self.max_distance
+            && self
+                .nearest
+                .is_none_or(|nearest| distance < nearest.distance)
+        {
+            let point = self.origin + self.direction * distance;
+            self.nearest = Some(CastHit {
+                target,
+                distance,
+                point,
+                normal: normal(point),
+            });
+        }
+    }
+}
@@ -1,20 +1,68 @@
 use bevy::{
-    ecs::system::{StaticSystemParam, SystemParamItem},
+    ecs::{
+        entity::EntityHashMap,
+        system::{StaticSystemParam, SystemParamItem},
+    },
     math::CompassQuadrant,
     prelude::*,
+    utils::Parallel,
 };
 
+use smallvec::SmallVec;
+
 use crate::{
-    Agent, Layer, Velocity,
+    Agent, Layer, Mass, Velocity,
     agent::AgentState,
-    tile::{TileIndex, TileMap},
+    cache::TileCache,
+    obstacle::{Obstacle, ObstacleIndex, ObstacleState},
+    sensor::Sensor,
+    tile::{Tile, TileIndex, TileMap, TileRegion, Wall},
 };
 
+/// A message written when an [`Agent`]'s movement is blocked during a simulation step.
+///
+/// When two moving agents collide, a message is written for each of them. An agent may collide several times in a
+/// single step, up to [`Layer::collision_iterations`].
+#[derive(Clone, Copy, Debug, Message, PartialEq)]
+pub struct AgentCollided {
+    /// The agent whose movement was blocked.
+    pub agent: Entity,
+    /// The agent or wall that blocked the movement.
+    pub other: CollisionTarget,
+    /// The contact normal, pointing away from `other` towards `agent`.
+    pub normal: Vec2,
+    /// The time since the start of the simulation step at which contact occurred, in seconds.
+    pub time_of_impact: f32,
+    /// The speed at which the agent was approaching `other` along the contact normal, in units per second.
+    pub relative_speed: f32,
+}
+
+/// The object an [`Agent`] collided with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionTarget {
+    /// Another agent.
+    Agent(Entity),
+    /// The edge of a solid tile.
+    Wall,
+    /// A static [`Obstacle`].
+    Obstacle(Entity),
+}
+
+// The earliest collision of an agent found within the remaining time of a step.
+struct NearestCollision<'a> {
+    remaining: f32,
+    nearest: Option<(Collision<'a>, f32)>,
+}
+
 enum Collision<'a> {
-    Agent(&'a AgentState),
-    Wall(CompassQuadrant),
+    // The target's position and velocity at the start of the step.
+    Agent(Entity, Vec2, Vec2, Option<&'a Mass>),
+    Wall(Vec2),
+    Corner(Vec2),
+    Obstacle(Entity, Vec2),
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub(crate) fn process<T>(
     index: Res<TileIndex>,
     mut agents: Query<(
@@ -24,124 +72,488 @@ pub(crate) fn process<T>(
         &AgentState,
         &mut Velocity,
         &ChildOf,
+        Option<&Mass>,
+        Has<Sensor>,
     )>,
-    targets: Query<(&Agent, &AgentState)>,
+    targets: Query<(&Agent, &AgentState, Option<&Mass>, Has<Sensor>)>,
+    obstacle_index: Res<ObstacleIndex>,
+    obstacles: Query<(&Obstacle, &ObstacleState)>,
     layers: Query<&Layer>,
+    caches: Query<&TileCache>,
     time: Res<Time>,
     map: StaticSystemParam<T>,
+    mut speeds: Local<EntityHashMap<f32>>,
+    mut swept_index: Local<TileIndex>,
+    mut collisions: Local<Parallel<Vec<AgentCollided>>>,
+    mut writer: MessageWriter<AgentCollided>,
+    #[cfg(feature = "diagnostic")] mut moving_agents: Local<Parallel<usize>>,
+    #[cfg(feature = "diagnostic")] mut diagnostics: bevy::diagnostic::Diagnostics,
 ) where
     T: TileMap,
     for<'w, 's> SystemParamItem<'w, 's, T>: TileMap,
 {
+    // The speed multiplier of each agent's tile is read once, as it may be needed by many other agents.
+    speeds.clear();
+    // Agents moving more than half a tile in a step may collide with agents outside the neighborhood of their own
+    // tile, so they are also indexed in the neighborhood of every tile they pass through.
+    swept_index.clear();
+    for (id, _, _, state, ..) in &agents {
+        let Some(tile) = state.tile else {
+            continue;
+        };
+        let Ok(layer) = layers.get(tile.layer()) else {
+            continue;
+        };
+
+        let speed = map.speed_multiplier(tile.layer(), tile.tile());
+        debug_assert!(speed > 0.0, "speed_multiplier must be positive");
+        // Agents in tiles with an invalid multiplier don't move, rather than moving by an invalid distance.
+        let speed = if speed > 0.0 && speed.is_finite() {
+            speed
+        } else {
+            0.0
+        };
+        speeds.insert(id, speed);
+
+        let motion = state.velocity * speed * time.delta_secs();
+        if motion.length() > layer.tile_size() * 0.5 {
+            let path = Tile::traverse_segment(
+                tile.layer(),
+                layer,
+                state.position,
+                state.position + motion,
+            );
+            swept_index.insert_path(id, path.map(|(tile, ..)| tile), state.reach);
+        }
+    }
+    let (speeds, swept_index) = (&*speeds, &*swept_index);
+
     agents.par_iter_mut().for_each(
-        |(id, agent, mut transform, position, mut velocity, parent)| {
-            if velocity.0 == Vec2::ZERO {
+        |(id, agent, mut transform, state, mut velocity, parent, mass, is_sensor)| {
+            // Stationary agents can't collide with anything, unless they may be pushed by other agents.
+            if velocity.0 == Vec2::ZERO && mass.is_none() {
                 return;
             }
 
-            let Some(tile) = position.tile else {
+            let Ok(layer) = layers.get(parent.0) else {
                 return;
             };
 
-            let Ok(layer) = layers.get(parent.0) else {
+            // The terrain scales how far the agent moves, but not the velocity it is trying to move at.
+            let Some(&speed) = speeds.get(&id) else {
                 return;
             };
+            #[cfg(feature = "diagnostic")]
+            moving_agents.scope(|count| *count += 1);
+            let initial_velocity = state.velocity * speed;
 
-            let mut nearest_collision: Option<(Collision, f32)> = None;
+            let mut position = state.position;
+            let mut current_velocity = initial_velocity;
+            let mut elapsed = 0.0;
+            let mut pushed: SmallVec<[Entity; 4]> = SmallVec::new();
 
-            for &target in index.get(tile).iter() {
-                if target == id {
-                    continue;
-                }
+            // Agents larger than half a tile must check for collisions with tiles beyond their own.
+            let neighborhood = state.reach - 1;
 
-                let Ok((target_agent, target_position)) = targets.get(target) else {
-                    continue;
-                };
+            // The walls around the agent's path are read once, and only read again if it is deflected outside them.
+            let mut region = None;
 
-                if let Some(t) = agent_collision(
-                    target_position.position - position.position,
-                    target_position.velocity - position.velocity,
-                    agent.radius() + target_agent.radius(),
-                ) {
-                    if t < time.delta_secs() {
-                        match nearest_collision {
-                            None => {
-                                nearest_collision = Some((Collision::Agent(target_position), t))
-                            }
-                            Some((_, current_t)) if t < current_t => {
-                                nearest_collision = Some((Collision::Agent(target_position), t));
+            for _ in 0..layer.collision_iterations() {
+                let remaining = time.delta_secs() - elapsed;
+                let mut nearest_collision = NearestCollision::new(remaining);
+
+                // Check the tiles the agent passes through this step in order, so fast agents don't pass through
+                // others, stopping once no later collision can be earlier than the nearest found.
+                let end = position + current_velocity * remaining;
+                let mut previous_tile: Option<IVec2> = None;
+                let mut swept_targets: SmallVec<[Entity; 4]> = SmallVec::new();
+                let mut inside_wall = false;
+                for (path_tile, entered, segment_start, segment_end) in
+                    Tile::traverse_segment(parent.0, layer, position, end)
+                {
+                    if nearest_collision.is_before(entered, current_velocity) {
+                        break;
+                    }
+
+                    let (min, max) = (
+                        path_tile.tile() - IVec2::splat(neighborhood),
+                        path_tile.tile() + IVec2::splat(neighborhood),
+                    );
+
+                    let indexed = index
+                        .query(parent.0, min, max, |target| {
+                            let data @ (_, target_state, ..) = targets.get(target).ok()?;
+                            Some((data, target_state.tile?.tile()))
+                        })
+                        .filter(|(_, (_, target_state, ..))| {
+                            // The path enters the neighborhood of each target at most once, so targets near the
+                            // previous tile were already checked from there.
+                            previous_tile.is_none_or(|previous_tile| {
+                                target_state.tile.is_none_or(|target_tile| {
+                                    previous_tile.chebyshev_distance(target_tile.tile())
+                                        > (neighborhood + target_state.reach) as u32
+                                })
+                            })
+                        });
+                    // Fast agents are indexed along their whole path, so may be found from many tiles.
+                    let swept = Tile::rect(parent.0, min, max)
+                        .flat_map(|query_tile| swept_index.get(query_tile))
+                        .filter_map(|&target| {
+                            if swept_targets.contains(&target) {
+                                return None;
                             }
-                            _ => {}
+                            swept_targets.push(target);
+                            Some((target, targets.get(target).ok()?))
+                        });
+
+                    for (target, (target_agent, target_state, target_mass, target_is_sensor)) in
+                        indexed.chain(swept)
+                    {
+                        if target == id || pushed.contains(&target) {
+                            continue;
+                        }
+
+                        // Sensors never block, or are blocked by, other agents.
+                        if is_sensor || target_is_sensor {
+                            continue;
+                        }
+
+                        if !agent
+                            .collision_groups()
+                            .collides_with(target_agent.collision_groups())
+                        {
+                            continue;
+                        }
+
+                        let Some(&target_speed) = speeds.get(&target) else {
+                            continue;
+                        };
+
+                        let target_velocity = target_state.velocity * target_speed;
+                        if let Some(t) = agent_collision(
+                            target_state.position + target_velocity * elapsed - position,
+                            target_velocity - current_velocity,
+                            agent.radius() + target_agent.radius(),
+                        ) {
+                            nearest_collision.hit(
+                                Collision::Agent(
+                                    target,
+                                    target_state.position,
+                                    target_velocity,
+                                    target_mass,
+                                ),
+                                t,
+                            );
                         }
                     }
-                }
-            }
 
-            for (wall_position, wall_normal) in tile.boundaries(&*map) {
-                if let Some(t) = wall_collision(
-                    position.position,
-                    position.velocity,
-                    agent.radius(),
-                    wall_position,
-                    wall_normal,
-                    layer.tile_size(),
-                ) {
-                    if t < time.delta_secs() {
-                        match nearest_collision {
-                            None => nearest_collision = Some((Collision::Wall(wall_normal), t)),
-                            Some((_, current_t)) if t < current_t => {
-                                nearest_collision = Some((Collision::Wall(wall_normal), t));
-                            }
-                            _ => {}
+                    if agent.collision_groups().collides_with_walls() {
+                        obstacle_collision_nearest(
+                            &mut nearest_collision,
+                            &obstacle_index,
+                            &obstacles,
+                            parent.0,
+                            layer,
+                            agent.radius(),
+                            position,
+                            current_velocity,
+                            (segment_start, segment_end),
+                        );
+                        if !inside_wall {
+                            inside_wall = wall_collision_nearest(
+                                &mut nearest_collision,
+                                &mut region,
+                                (&*map, caches.get(parent.0).ok()),
+                                parent.0,
+                                layer,
+                                agent.radius(),
+                                position,
+                                current_velocity,
+                                (segment_start, segment_end),
+                            );
                         }
                     }
+
+                    previous_tile = Some(path_tile.tile());
                 }
-            }
 
-            if let Some((nearest, t)) = nearest_collision {
-                let (new_position, normal) = nearest.contact(position, t.max(0.));
-                let projected_velocity = position.velocity.dot(normal);
-                if projected_velocity < 0.0 {
-                    velocity.0 -= projected_velocity * normal;
+                let Some((nearest, t)) = nearest_collision.nearest else {
+                    position += current_velocity * remaining;
+                    break;
+                };
+
+                let t = t.max(0.);
+                let (contact, normal) = nearest.contact(position, current_velocity, elapsed, t);
+                let relative_speed = -(current_velocity - nearest.velocity()).dot(normal);
+
+                let projected_velocity = current_velocity.dot(normal);
+                if let Collision::Agent(target, _, target_velocity, Some(target_mass)) = nearest
+                    && let Some(mass) = mass.and_then(Mass::get)
+                    && let Some(target_mass) = target_mass.get()
+                {
+                    // Both agents continue along the normal with their combined momentum, so the lighter agent is
+                    // pushed aside. The target moves with this agent for the rest of the step, so ignore it.
+                    let target_projected_velocity = target_velocity.dot(normal);
+                    if projected_velocity < target_projected_velocity {
+                        let combined_velocity = (mass * projected_velocity
+                            + target_mass * target_projected_velocity)
+                            / (mass + target_mass);
+                        current_velocity += (combined_velocity - projected_velocity) * normal;
+                        pushed.push(target);
+                    }
+                } else if projected_velocity < 0.0 {
+                    current_velocity -= projected_velocity * normal;
                 }
 
-                transform.translation.x = new_position.x;
-                transform.translation.y = new_position.y;
-            } else {
-                let new_position = position.position + position.velocity * time.delta_secs();
-                transform.translation.x = new_position.x;
-                transform.translation.y = new_position.y;
+                position = contact;
+                elapsed += t;
+
+                collisions.borrow_local_mut().push(AgentCollided {
+                    agent: id,
+                    other: nearest.target(),
+                    normal,
+                    time_of_impact: elapsed,
+                    relative_speed,
+                });
+            }
+
+            if position != state.position {
+                transform.translation.x = position.x;
+                transform.translation.y = position.y;
+            }
+
+            if current_velocity != initial_velocity && speed > 0.0 {
+                velocity.0 = current_velocity / speed;
             }
         },
     );
+
+    writer.write_batch(collisions.drain());
+
+    #[cfg(feature = "diagnostic")]
+    {
+        let count: usize = moving_agents.iter_mut().map(std::mem::take).sum();
+        diagnostics.add_measurement(&crate::diagnostic::MOVING_AGENTS, || count as f64);
+    }
+}
+
+// Finds the earliest collision of an agent with the obstacles around part of its path within the remaining time.
+#[allow(clippy::too_many_arguments)]
+fn obstacle_collision_nearest(
+    nearest_collision: &mut NearestCollision,
+    index: &ObstacleIndex,
+    obstacles: &Query<(&Obstacle, &ObstacleState)>,
+    layer_id: Entity,
+    layer: &Layer,
+    radius: f32,
+    position: Vec2,
+    current_velocity: Vec2,
+    (segment_start, segment_end): (Vec2, Vec2),
+) {
+    let min = Tile::floor(
+        layer_id,
+        segment_start.min(segment_end) - radius,
+        layer.scale(),
+    )
+    .tile();
+    let max = Tile::floor(
+        layer_id,
+        segment_start.max(segment_end) + radius,
+        layer.scale(),
+    )
+    .tile();
+
+    for (target, obstacle, state) in index.query(obstacles, layer_id, min, max) {
+        if let Some(t) = agent_collision(
+            state.position - position,
+            -current_velocity,
+            radius + obstacle.radius(),
+        ) {
+            nearest_collision.hit(Collision::Obstacle(target, state.position), t);
+        }
+    }
+}
+
+// Finds the earliest collision of an agent with the walls around part of its path within the remaining time. Returns
+// `true` if the agent is inside a solid tile, in which case the rest of its path need not be checked.
+#[allow(clippy::too_many_arguments)]
+fn wall_collision_nearest(
+    nearest_collision: &mut NearestCollision,
+    region: &mut Option<TileRegion>,
+    (map, cache): (&impl TileMap, Option<&TileCache>),
+    layer_id: Entity,
+    layer: &Layer,
+    radius: f32,
+    position: Vec2,
+    current_velocity: Vec2,
+    (segment_start, segment_end): (Vec2, Vec2),
+) -> bool {
+    let tile = Tile::floor(layer_id, position, layer.scale());
+
+    let min = Tile::floor(
+        layer_id,
+        segment_start.min(segment_end) - radius,
+        layer.scale(),
+    )
+    .tile();
+    let max = Tile::floor(
+        layer_id,
+        segment_start.max(segment_end) + radius,
+        layer.scale(),
+    )
+    .tile();
+    let region = match region {
+        Some(region) if region.contains(min, max) => region,
+        _ => {
+            // Read the walls around the rest of the agent's path as well, so they don't need to be read again for
+            // its later parts.
+            let end = position + current_velocity * nearest_collision.remaining;
+            let (path_min, path_max) = (
+                Tile::floor(layer_id, position.min(end) - radius, layer.scale()).tile(),
+                Tile::floor(layer_id, position.max(end) + radius, layer.scale()).tile(),
+            );
+            region.insert(TileRegion::new(
+                map,
+                cache,
+                layer_id,
+                min.min(path_min),
+                max.max(path_max),
+            ))
+        }
+    };
+
+    // Only the first part of the path contains the agent's position.
+    if segment_start == position && region.is_solid(tile.tile()) {
+        // The agent is inside a solid tile, so only prevent it moving further inside.
+        for (wall_position, wall_normal) in region.boundaries(tile.tile()) {
+            if let Some(t) = wall_collision(
+                position,
+                current_velocity,
+                radius,
+                wall_position,
+                wall_normal,
+                layer.tile_size(),
+            ) {
+                nearest_collision.hit(Collision::Wall(normal_vector(wall_normal)), t);
+            }
+        }
+        return true;
+    }
+
+    for wall in region.walls_in(min, max) {
+        let (collision, t) = match wall {
+            Wall::Edge(wall_tile, wall_normal) => (
+                Collision::Wall(normal_vector(wall_normal)),
+                edge_collision(
+                    position,
+                    current_velocity,
+                    radius,
+                    wall_tile,
+                    wall_normal,
+                    layer.tile_size(),
+                ),
+            ),
+            Wall::Corner(corner) => {
+                let corner = corner.as_vec2() * layer.tile_size();
+                (
+                    Collision::Corner(corner),
+                    agent_collision(corner - position, -current_velocity, radius),
+                )
+            }
+            Wall::Face(start, end, wall_normal) => (
+                Collision::Wall(wall_normal),
+                face_collision(
+                    position,
+                    current_velocity,
+                    radius,
+                    start * layer.tile_size(),
+                    end * layer.tile_size(),
+                    wall_normal,
+                ),
+            ),
+            Wall::Vertex(vertex) => {
+                let vertex = vertex * layer.tile_size();
+                (
+                    Collision::Corner(vertex),
+                    agent_collision(vertex - position, -current_velocity, radius),
+                )
+            }
+        };
+
+        if let Some(t) = t {
+            nearest_collision.hit(collision, t);
+        }
+    }
+
+    false
+}
+
+impl<'a> NearestCollision<'a> {
+    fn new(remaining: f32) -> Self {
+        NearestCollision {
+            remaining,
+            nearest: None,
+        }
+    }
+
+    // Returns `true` if the nearest collision found happens before an agent moving with the given velocity has
+    // travelled the given distance.
+    fn is_before(&self, distance: f32, velocity: Vec2) -> bool {
+        self.nearest
+            .as_ref()
+            .is_some_and(|&(_, t)| t * velocity.length() < distance)
+    }
+
+    // Records a collision at time `t`, if it is within the remaining time and earlier than any found so far.
+    fn hit(&mut self, collision: Collision<'a>, t: f32) {
+        if t < self.remaining
+            && self
+                .nearest
+                .as_ref()
+                .is_none_or(|&(_, current_t)| t < current_t)
+        {
+            self.nearest = Some((collision, t));
+        }
+    }
 }
 
 impl Collision<'_> {
-    fn contact(&self, agent: &AgentState, t: f32) -> (Vec2, Vec2) {
-        let agent_contact = agent.position + agent.velocity * t;
+    fn contact(&self, position: Vec2, velocity: Vec2, elapsed: f32, t: f32) -> (Vec2, Vec2) {
+        let agent_contact = position + velocity * t;
         match self {
-            Collision::Agent(target) => {
-                let target_contact = target.position + target.velocity * t;
+            Collision::Agent(_, target_position, target_velocity, _) => {
+                let target_contact = *target_position + *target_velocity * (elapsed + t);
 
                 let normal = (agent_contact - target_contact).normalize_or_zero();
 
                 (agent_contact, normal)
             }
-            Collision::Wall(normal) => {
-                let normal = match normal {
-                    CompassQuadrant::North => Vec2::Y,
-                    CompassQuadrant::East => Vec2::X,
-                    CompassQuadrant::South => -Vec2::Y,
-                    CompassQuadrant::West => -Vec2::X,
-                };
+            Collision::Wall(normal) => (agent_contact, *normal),
+            Collision::Corner(center) | Collision::Obstacle(_, center) => {
+                let normal = (agent_contact - *center).normalize_or_zero();
 
                 (agent_contact, normal)
             }
         }
     }
+
+    fn target(&self) -> CollisionTarget {
+        match self {
+            Collision::Agent(id, ..) => CollisionTarget::Agent(*id),
+            Collision::Wall(_) | Collision::Corner(_) => CollisionTarget::Wall,
+            Collision::Obstacle(id, _) => CollisionTarget::Obstacle(*id),
+        }
+    }
+
+    fn velocity(&self) -> Vec2 {
+        match self {
+            Collision::Agent(_, _, target_velocity, _) => *target_velocity,
+            Collision::Wall(_) | Collision::Corner(_) | Collision::Obstacle(..) => Vec2::ZERO,
+        }
+    }
 }
 
-fn agent_collision(
+pub(crate) fn agent_collision(
     delta_position: Vec2,
     delta_velocity: Vec2,
     combined_radius: f32,
@@ -190,8 +602,86 @@ fn wall_collision(
     if projected_velocity > 0.0 {
         Some((delta_position - agent_radius) / projected_velocity)
     } else {
+        None
+    }
+}
+
+// Returns the time of collision with the edge of the given open tile, facing in the direction of `wall_normal`.
+pub(crate) fn edge_collision(
+    agent_position: Vec2,
+    agent_velocity: Vec2,
+    agent_radius: f32,
+    tile: IVec2,
+    wall_normal: CompassQuadrant,
+    tile_size: f32,
+) -> Option<f32> {
+    let (wall_position, wall_start) = match wall_normal {
+        CompassQuadrant::North => (tile.y, tile.x),
+        CompassQuadrant::East => (tile.x, tile.y),
+        CompassQuadrant::South => (tile.y + 1, tile.x),
+        CompassQuadrant::West => (tile.x + 1, tile.y),
+    };
+
+    let t = wall_collision(
+        agent_position,
+        agent_velocity,
+        agent_radius,
+        wall_position,
+        wall_normal,
+        tile_size,
+    )?;
+
+    // Ignore contacts beyond the ends of the edge, or from the solid side of it.
+    let contact = agent_position + agent_velocity * t.max(0.);
+    let (distance, tangent) = match wall_normal {
+        CompassQuadrant::North => (contact.y - wall_position as f32 * tile_size, contact.x),
+        CompassQuadrant::East => (contact.x - wall_position as f32 * tile_size, contact.y),
+        CompassQuadrant::South => (wall_position as f32 * tile_size - contact.y, contact.x),
+        CompassQuadrant::West => (wall_position as f32 * tile_size - contact.x, contact.y),
+    };
+    let wall_start = wall_start as f32 * tile_size;
+    if distance >= 0.0 && (wall_start..=wall_start + tile_size).contains(&tangent) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+// Returns the time of collision with a face of a partially solid tile between two points, facing in the direction of
+// `wall_normal`.
+pub(crate) fn face_collision(
+    agent_position: Vec2,
+    agent_velocity: Vec2,
+    agent_radius: f32,
+    start: Vec2,
+    end: Vec2,
+    wall_normal: Vec2,
+) -> Option<f32> {
+    let projected_velocity = -agent_velocity.dot(wall_normal);
+    if projected_velocity <= 0.0 {
         return None;
     }
+
+    let t = ((agent_position - start).dot(wall_normal) - agent_radius) / projected_velocity;
+
+    // Ignore contacts beyond the ends of the face, or from the solid side of it.
+    let contact = agent_position + agent_velocity * t.max(0.);
+    let distance = (contact - start).dot(wall_normal);
+    let tangent = (contact - start).dot(end - start) / start.distance_squared(end);
+    if distance >= 0.0 && (0.0..=1.0).contains(&tangent) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn normal_vector(normal: CompassQuadrant) -> Vec2 {
+    match normal {
+        CompassQuadrant::North => Vec2::Y,
+        CompassQuadrant::East => Vec2::X,
+        CompassQuadrant::South => -Vec2::Y,
+        CompassQuadrant::West => -Vec2::X,
+    }
 }
 
 #[cfg(test)]
@@ -453,4 +943,86 @@ mod tests {
         .unwrap();
         assert_relative_eq!(t, -0.3);
     }
+
+    #[test]
+    fn edge_collision_closing() {
+        let t = edge_collision(
+            Vec2::new(0.5, 1.5),
+            Vec2::new(0.0, -1.0),
+            0.2,
+            IVec2::new(0, 1),
+            CompassQuadrant::North,
+            1.0,
+        )
+        .unwrap();
+        assert_relative_eq!(t, 0.3);
+    }
+
+    #[test]
+    fn edge_collision_oblique() {
+        let t = edge_collision(
+            Vec2::new(0.5, 0.5),
+            Vec2::new(1.0, 1.0),
+            0.2,
+            IVec2::new(0, 0),
+            CompassQuadrant::West,
+            1.0,
+        )
+        .unwrap();
+        assert_relative_eq!(t, 0.3);
+    }
+
+    #[test]
+    fn edge_collision_beyond_end() {
+        let t = edge_collision(
+            Vec2::new(0.5, 0.5),
+            Vec2::new(1.0, 2.0),
+            0.2,
+            IVec2::new(0, 0),
+            CompassQuadrant::West,
+            1.0,
+        );
+        assert!(t.is_none());
+    }
+
+    #[test]
+    fn edge_collision_different_tile_size() {
+        let t = edge_collision(
+            Vec2::new(-1.0, 0.5),
+            Vec2::new(-1.0, 0.0),
+            0.2,
+            IVec2::new(-1, 0),
+            CompassQuadrant::East,
+            2.0,
+        )
+        .unwrap();
+        assert_relative_eq!(t, 0.8);
+    }
+
+    #[test]
+    fn edge_collision_behind() {
+        let t = edge_collision(
+            Vec2::new(0.5, 0.7),
+            Vec2::new(0.0, -1.0),
+            0.2,
+            IVec2::new(0, 1),
+            CompassQuadrant::North,
+            1.0,
+        );
+        assert!(t.is_none());
+    }
+
+    #[test]
+    fn edge_collision_intersecting_and_closing() {
+        let t = edge_collision(
+            Vec2::new(0.5, 1.1),
+            Vec2::new(0.0, -1.0),
+            0.2,
+            IVec2::new(0, 1),
+            CompassQuadrant::North,
+            1.0,
+        )
+        .unwrap();
+        assert_relative_eq!(t, -0.1);
+    }
 }
@@ -0,0 +1,231 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    ecs::system::{StaticSystemParam, SystemParamItem},
+    math::CompassQuadrant,
+    platform::collections::HashSet,
+    prelude::*,
+};
+
+use crate::{
+    Agent, AgentCollided, Layer, TileMap, Velocity,
+    cache::TileCache,
+    tile::{INDEX_INLINE_CAPACITY, Tile, TileIndex, TileRegion, Wall},
+};
+
+const AGENT_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+const VELOCITY_COLOR: Color = Color::srgb(0.2, 0.9, 0.3);
+const WALL_COLOR: Color = Color::srgb(1.0, 0.6, 0.1);
+const INDEX_COLOR: Color = Color::srgb(0.2, 0.5, 1.0);
+// The color of tiles with more agents than can be indexed without allocating.
+const INDEX_FULL_COLOR: Color = Color::srgb(1.0, 0.2, 0.9);
+const CONTACT_COLOR: Color = Color::srgb(1.0, 0.2, 0.2);
+
+/// Plugin for drawing the state of [`jostle`](crate) with gizmos, for layers with a [`JostleDebug`] component.
+///
+/// Requires Bevy's `GizmoPlugin`, which is included in `DefaultPlugins`.
+#[derive(Debug)]
+pub struct JostleDebugPlugin<T> {
+    marker: PhantomData<T>,
+}
+
+/// A component which enables drawing a [`Layer`] with the [`JostleDebugPlugin`], and selects what is drawn.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JostleDebug {
+    /// Draw the circle and velocity of each agent.
+    pub agents: bool,
+    /// Draw the walls near each agent.
+    pub walls: bool,
+    /// Draw the tiles each agent is indexed in, shaded by the number of agents.
+    pub index: bool,
+    /// Draw the normal of each collision in the most recent simulation steps.
+    pub contacts: bool,
+}
+
+impl<T> Plugin for JostleDebugPlugin<T>
+where
+    T: TileMap + 'static,
+    for<'w, 's> SystemParamItem<'w, 's, T>: TileMap,
+{
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            (draw_agents, draw_walls::<T>, draw_index, draw_contacts)
+                .after(TransformSystems::Propagate),
+        );
+    }
+}
+
+impl<T> Default for JostleDebugPlugin<T> {
+    fn default() -> Self {
+        JostleDebugPlugin {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl Default for JostleDebug {
+    fn default() -> Self {
+        JostleDebug {
+            agents: true,
+            walls: true,
+            index: false,
+            contacts: true,
+        }
+    }
+}
+
+fn draw_agents(
+    mut gizmos: Gizmos,
+    layers: Query<(&JostleDebug, &GlobalTransform), With<Layer>>,
+    agents: Query<(&Agent, &Transform, &Velocity, &ChildOf)>,
+) {
+    for (agent, transform, velocity, parent) in &agents {
+        let Ok((debug, layer_transform)) = layers.get(parent.0) else {
+            continue;
+        };
+        if !debug.agents {
+            continue;
+        }
+
+        let position = transform.translation.xy();
+        let center = to_world(layer_transform, position);
+        gizmos.circle_2d(
+            center,
+            agent.radius() * layer_transform.scale().x,
+            AGENT_COLOR,
+        );
+        if velocity.0 != Vec2::ZERO {
+            gizmos.arrow_2d(
+                center,
+                to_world(layer_transform, position + velocity.0),
+                VELOCITY_COLOR,
+            );
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn draw_walls<T>(
+    mut gizmos: Gizmos,
+    layers: Query<(&Layer, &JostleDebug, &GlobalTransform, Option<&TileCache>)>,
+    agents: Query<(&Agent, &Transform, &ChildOf)>,
+    map: StaticSystemParam<T>,
+    mut tiles: Local<HashSet<(Entity, IVec2)>>,
+) where
+    T: TileMap,
+    for<'w, 's> SystemParamItem<'w, 's, T>: TileMap,
+{
+    // Find the tiles around each agent, so walls shared by several agents are only drawn once.
+    tiles.clear();
+    for (agent, transform, parent) in &agents {
+        let Ok((layer, debug, ..)) = layers.get(parent.0) else {
+            continue;
+        };
+        if !debug.walls {
+            continue;
+        }
+
+        let tile = Tile::floor(parent.0, transform.translation.xy(), layer.scale());
+        for tile in tile.neighborhood(Tile::reach(agent.radius(), layer.scale())) {
+            tiles.insert((parent.0, tile.tile()));
+        }
+    }
+
+    for &(layer_id, tile) in tiles.iter() {
+        let Ok((layer, _, layer_transform, cache)) = layers.get(layer_id) else {
+            continue;
+        };
+
+        let tile_size = layer.tile_size();
+        for wall in TileRegion::new(&*map, cache, layer_id, tile, tile).walls() {
+            let (start, end) = match wall {
+                Wall::Edge(tile, normal) => {
+                    let tile = tile.as_vec2();
+                    match normal {
+                        CompassQuadrant::North => (tile, tile + Vec2::X),
+                        CompassQuadrant::East => (tile, tile + Vec2::Y),
+                        CompassQuadrant::South => (tile + Vec2::Y, tile + Vec2::ONE),
+                        CompassQuadrant::West => (tile + Vec2::X, tile + Vec2::ONE),
+                    }
+                }
+                Wall::Face(start, end, _) => (start, end),
+                Wall::Corner(_) | Wall::Vertex(_) => continue,
+            };
+            gizmos.line_2d(
+                to_world(layer_transform, start * tile_size),
+                to_world(layer_transform, end * tile_size),
+                WALL_COLOR,
+            );
+        }
+    }
+}
+
+fn draw_index(
+    mut gizmos: Gizmos,
+    index: Res<TileIndex>,
+    layers: Query<(&Layer, &JostleDebug, &GlobalTransform)>,
+) {
+    for (tile, agents) in index.iter() {
+        let Ok((layer, debug, layer_transform)) = layers.get(tile.layer()) else {
+            continue;
+        };
+        if !debug.index {
+            continue;
+        }
+
+        let color = if agents.len() > INDEX_INLINE_CAPACITY {
+            INDEX_FULL_COLOR
+        } else {
+            INDEX_COLOR.with_alpha(agents.len() as f32 / INDEX_INLINE_CAPACITY as f32)
+        };
+
+        // Inset the outline slightly, so neighboring tiles can be told apart.
+        let min = (tile.tile().as_vec2() + 0.05) * layer.tile_size();
+        let max = (tile.tile().as_vec2() + 0.95) * layer.tile_size();
+        gizmos.linestrip_2d(
+            [
+                min,
+                Vec2::new(max.x, min.y),
+                max,
+                Vec2::new(min.x, max.y),
+                min,
+            ]
+            .map(|corner| to_world(layer_transform, corner)),
+            color,
+        );
+    }
+}
+
+fn draw_contacts(
+    mut gizmos: Gizmos,
+    mut reader: MessageReader<AgentCollided>,
+    layers: Query<(&JostleDebug, &GlobalTransform), With<Layer>>,
+    agents: Query<(&Agent, &Transform, &ChildOf)>,
+) {
+    for collision in reader.read() {
+        let Ok((agent, transform, parent)) = agents.get(collision.agent) else {
+            continue;
+        };
+        let Ok((debug, layer_transform)) = layers.get(parent.0) else {
+            continue;
+        };
+        if !debug.contacts {
+            continue;
+        }
+
+        // The normal points away from the other object, so draw it from the contact point on the agent's edge.
+        let position = transform.translation.xy();
+        let contact = position - collision.normal * agent.radius();
+        gizmos.arrow_2d(
+            to_world(layer_transform, contact),
+            to_world(layer_transform, position),
+            CONTACT_COLOR,
+        );
+    }
+}
+
+// Converts a position within a layer to world coordinates.
+fn to_world(layer_transform: &GlobalTransform, position: Vec2) -> Vec2 {
+    layer_transform.transform_point(position.extend(0.0)).xy()
+}
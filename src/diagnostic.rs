@@ -7,23 +7,86 @@ use bevy::{
     prelude::*,
 };
 
+use crate::{
+    AgentCollided, CollisionTarget,
+    tile::{TileChanged, TileIndex},
+};
+
+/// The time taken to record the position of each agent at the start of a step, for interpolation.
 pub const UPDATE_FIXED_POSITION: DiagnosticPath =
     DiagnosticPath::const_new("jostle/update_fixed_position");
+/// The time taken to move agents out of tiles which became solid.
+pub const REVALIDATE_AGENTS: DiagnosticPath = DiagnosticPath::const_new("jostle/revalidate_agents");
+/// The time taken to find the tile containing each agent.
 pub const UPDATE_AGENT_TILE: DiagnosticPath = DiagnosticPath::const_new("jostle/update_agent_tile");
+/// The time taken to fill and invalidate the walls in each [`TileCache`](crate::TileCache).
+pub const UPDATE_TILE_CACHE: DiagnosticPath = DiagnosticPath::const_new("jostle/update_tile_cache");
+/// The time taken to interpolate the rendered position of each agent between steps.
 pub const UPDATE_RENDER_POSITION: DiagnosticPath =
     DiagnosticPath::const_new("jostle/update_render_position");
+/// The time taken to move agents between tiles in the index used to find nearby agents.
 pub const UPDATE_TILE_INDEX: DiagnosticPath = DiagnosticPath::const_new("jostle/update_tile_index");
+/// The time taken to move obstacles between tiles in the index used to find nearby obstacles.
+pub const UPDATE_OBSTACLE_INDEX: DiagnosticPath =
+    DiagnosticPath::const_new("jostle/update_obstacle_index");
+/// The time taken to find the agents entering and exiting each sensor.
+pub const UPDATE_SENSORS: DiagnosticPath = DiagnosticPath::const_new("jostle/update_sensors");
+/// The time taken to move agents and resolve their collisions.
 pub const PROCESS_COLLISIONS: DiagnosticPath =
     DiagnosticPath::const_new("jostle/process_collisions");
+/// The time taken to push apart overlapping agents.
+pub const SEPARATE_AGENTS: DiagnosticPath = DiagnosticPath::const_new("jostle/separate_agents");
+
+/// The number of moving agents checked for collisions in the most recent step.
+pub const MOVING_AGENTS: DiagnosticPath = DiagnosticPath::const_new("jostle/moving_agents");
+/// The number of collisions between agents resolved in the most recent step, counted once for each moving agent.
+pub const AGENT_CONTACTS: DiagnosticPath = DiagnosticPath::const_new("jostle/agent_contacts");
+/// The number of collisions between agents and walls resolved in the most recent step.
+pub const WALL_CONTACTS: DiagnosticPath = DiagnosticPath::const_new("jostle/wall_contacts");
+/// The number of collisions between agents and obstacles resolved in the most recent step.
+pub const OBSTACLE_CONTACTS: DiagnosticPath = DiagnosticPath::const_new("jostle/obstacle_contacts");
+/// The number of times an agent moved between tiles in the most recent step.
+pub const TILE_CHANGES: DiagnosticPath = DiagnosticPath::const_new("jostle/tile_changes");
+/// The number of tiles with at least one agent indexed in them.
+pub const OCCUPIED_TILES: DiagnosticPath = DiagnosticPath::const_new("jostle/occupied_tiles");
+/// The largest number of agents indexed in a single tile, sampled every 16 steps.
+///
+/// Tiles with more than [`INDEX_INLINE_CAPACITY`] agents require an allocation.
+pub const MAX_TILE_AGENTS: DiagnosticPath = DiagnosticPath::const_new("jostle/max_tile_agents");
+
+/// The number of agents which can be indexed in a tile without allocating.
+pub const INDEX_INLINE_CAPACITY: usize = crate::tile::INDEX_INLINE_CAPACITY;
+
+// The number of steps between samples of `MAX_TILE_AGENTS`, which walks the whole index.
+const MAX_TILE_AGENTS_INTERVAL: u32 = 16;
+
+/// Every path measured by [`jostle`](crate) systems, in milliseconds.
+pub const PATHS: [DiagnosticPath; 10] = [
+    UPDATE_FIXED_POSITION,
+    REVALIDATE_AGENTS,
+    UPDATE_AGENT_TILE,
+    UPDATE_TILE_CACHE,
+    UPDATE_RENDER_POSITION,
+    UPDATE_TILE_INDEX,
+    UPDATE_OBSTACLE_INDEX,
+    UPDATE_SENSORS,
+    PROCESS_COLLISIONS,
+    SEPARATE_AGENTS,
+];
+
+/// Every path counted by [`jostle`](crate) systems.
+pub const COUNTS: [DiagnosticPath; 7] = [
+    MOVING_AGENTS,
+    AGENT_CONTACTS,
+    WALL_CONTACTS,
+    OBSTACLE_CONTACTS,
+    TILE_CHANGES,
+    OCCUPIED_TILES,
+    MAX_TILE_AGENTS,
+];
 
 pub(crate) fn register(app: &mut App) {
-    for path in [
-        UPDATE_FIXED_POSITION,
-        UPDATE_AGENT_TILE,
-        UPDATE_RENDER_POSITION,
-        UPDATE_TILE_INDEX,
-        PROCESS_COLLISIONS,
-    ] {
+    for path in PATHS {
         app.register_diagnostic(
             Diagnostic::new(path)
                 .with_suffix("ms")
@@ -31,6 +94,52 @@ pub(crate) fn register(app: &mut App) {
                 .with_smoothing_factor(0.06),
         );
     }
+
+    for path in COUNTS {
+        app.register_diagnostic(
+            Diagnostic::new(path)
+                .with_max_history_length(32)
+                .with_smoothing_factor(0.06),
+        );
+    }
+}
+
+pub(crate) fn count(
+    mut diagnostics: Diagnostics,
+    index: Res<TileIndex>,
+    mut tile_reader: MessageReader<TileChanged>,
+    mut collision_reader: MessageReader<AgentCollided>,
+    mut steps: Local<u32>,
+) {
+    let (mut agent_contacts, mut wall_contacts, mut obstacle_contacts) = (0, 0, 0);
+    for collision in collision_reader.read() {
+        match collision.other {
+            CollisionTarget::Agent(_) => agent_contacts += 1,
+            CollisionTarget::Wall => wall_contacts += 1,
+            CollisionTarget::Obstacle(_) => obstacle_contacts += 1,
+        }
+    }
+
+    let tile_changes = tile_reader.read().count();
+
+    let occupied_tiles = index.len();
+
+    diagnostics.add_measurement(&AGENT_CONTACTS, || agent_contacts as f64);
+    diagnostics.add_measurement(&WALL_CONTACTS, || wall_contacts as f64);
+    diagnostics.add_measurement(&OBSTACLE_CONTACTS, || obstacle_contacts as f64);
+    diagnostics.add_measurement(&TILE_CHANGES, || tile_changes as f64);
+    diagnostics.add_measurement(&OCCUPIED_TILES, || occupied_tiles as f64);
+
+    if steps.is_multiple_of(MAX_TILE_AGENTS_INTERVAL) {
+        diagnostics.add_measurement(&MAX_TILE_AGENTS, || {
+            index
+                .iter()
+                .map(|(_, agents)| agents.len())
+                .max()
+                .unwrap_or(0) as f64
+        });
+    }
+    *steps = steps.wrapping_add(1);
 }
 
 pub(crate) fn measure<S, M>(
@@ -0,0 +1,308 @@
+use bevy::{ecs::system::SystemParam, platform::collections::HashMap, prelude::*};
+
+use crate::TileMap;
+
+const CHUNK_SIZE: i32 = 32;
+
+/// A component storing which tiles of a [`Layer`](crate::Layer) are solid, for use with [`LayerGrid`].
+///
+/// Tiles are stored in fixed size chunks, which are only allocated once they contain a solid tile, so large sparse
+/// worlds use little memory. When tiles are changed after agents have been spawned, a
+/// [`TileMapChanged`](crate::TileMapChanged) message should be written so agents inside them are moved out.
+#[derive(Component, Clone, Debug, Default)]
+pub struct TileGrid {
+    chunks: HashMap<IVec2, Chunk>,
+}
+
+/// A [`TileMap`] which reads the [`TileGrid`] component of each [`Layer`](crate::Layer).
+///
+/// Layers without a [`TileGrid`] have no solid tiles.
+#[derive(SystemParam)]
+pub struct LayerGrid<'w, 's> {
+    grids: Query<'w, 's, &'static TileGrid>,
+}
+
+// A square of tiles, with a bit for each tile in each row.
+#[derive(Clone, Debug)]
+struct Chunk([u32; CHUNK_SIZE as usize]);
+
+impl TileGrid {
+    /// Creates a new [`TileGrid`] with no solid tiles.
+    pub fn new() -> Self {
+        TileGrid::default()
+    }
+
+    /// Returns `true` if the tile at the given coordinates is solid.
+    pub fn is_solid(&self, tile: IVec2) -> bool {
+        let (chunk, offset) = split(tile);
+        self.chunks
+            .get(&chunk)
+            .is_some_and(|chunk| chunk.0[offset.y as usize] & (1 << offset.x) != 0)
+    }
+
+    // Sets a bit for each solid tile in the rectangle between `min` and `max`, in the layout used by
+    // `TileMap::is_solid_region`. Each row is copied from the chunks it crosses a word at a time.
+    pub(crate) fn is_solid_region(&self, min: IVec2, max: IVec2, solid: &mut [u64]) {
+        let width = (max.x - min.x + 1) as usize;
+        for y in min.y..=max.y {
+            let row_start = (y - min.y) as usize * width;
+            let mut x = min.x;
+            while x <= max.x {
+                let (chunk, offset) = split(IVec2::new(x, y));
+                let len = (CHUNK_SIZE - offset.x).min(max.x - x + 1);
+                if let Some(chunk) = self.chunks.get(&chunk) {
+                    let mut bits =
+                        (chunk.0[offset.y as usize] >> offset.x) as u64 & ((1 << len) - 1);
+                    while bits != 0 {
+                        let i = row_start + (x - min.x) as usize + bits.trailing_zeros() as usize;
+                        solid[i / 64] |= 1 << (i % 64);
+                        bits &= bits - 1;
+                    }
+                }
+                x += len;
+            }
+        }
+    }
+
+    /// Returns the coordinates of every solid tile, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.chunks.iter().flat_map(|(&chunk, rows)| {
+            rows.0.iter().enumerate().flat_map(move |(y, &row)| {
+                (0..CHUNK_SIZE)
+                    .filter(move |x| row & (1 << x) != 0)
+                    .map(move |x| chunk * CHUNK_SIZE + IVec2::new(x, y as i32))
+            })
+        })
+    }
+
+    /// Returns the coordinates of every tile which is solid in exactly one of the two grids, in no particular order.
+    ///
+    /// This can be used to find the tiles for a [`TileMapChanged`](crate::TileMapChanged) message when replacing a
+    /// grid.
+    pub fn diff<'a>(&'a self, other: &'a TileGrid) -> impl Iterator<Item = IVec2> + 'a {
+        let added = other
+            .chunks
+            .keys()
+            .filter(|chunk| !self.chunks.contains_key(*chunk));
+        self.chunks.keys().chain(added).flat_map(move |&chunk| {
+            let (old, new) = (self.chunks.get(&chunk), other.chunks.get(&chunk));
+            (0..CHUNK_SIZE).flat_map(move |y| {
+                let row = |chunk: Option<&Chunk>| chunk.map_or(0, |chunk| chunk.0[y as usize]);
+                let changed = row(old) ^ row(new);
+                (0..CHUNK_SIZE)
+                    .filter(move |x| changed & (1 << x) != 0)
+                    .map(move |x| chunk * CHUNK_SIZE + IVec2::new(x, y))
+            })
+        })
+    }
+
+    /// Sets whether the tile at the given coordinates is solid.
+    pub fn set_solid(&mut self, tile: IVec2, solid: bool) {
+        self.fill_rect(tile, tile, solid);
+    }
+
+    /// Sets whether all tiles in the rectangle between `min` and `max`, inclusive, are solid.
+    pub fn fill_rect(&mut self, min: IVec2, max: IVec2, solid: bool) {
+        if min.x > max.x || min.y > max.y {
+            return;
+        }
+
+        let (min_chunk, _) = split(min);
+        let (max_chunk, _) = split(max);
+        for chunk_y in min_chunk.y..=max_chunk.y {
+            for chunk_x in min_chunk.x..=max_chunk.x {
+                let chunk_position = IVec2::new(chunk_x, chunk_y);
+                let origin = chunk_position * CHUNK_SIZE;
+                let start = (min - origin).max(IVec2::ZERO);
+                let end = (max - origin).min(IVec2::splat(CHUNK_SIZE - 1));
+
+                let mask = (u32::MAX >> (CHUNK_SIZE - 1 - end.x + start.x)) << start.x;
+                if solid {
+                    let chunk = self
+                        .chunks
+                        .entry(chunk_position)
+                        .or_insert(Chunk([0; CHUNK_SIZE as usize]));
+                    for row in &mut chunk.0[start.y as usize..=end.y as usize] {
+                        *row |= mask;
+                    }
+                } else if let Some(chunk) = self.chunks.get_mut(&chunk_position) {
+                    for row in &mut chunk.0[start.y as usize..=end.y as usize] {
+                        *row &= !mask;
+                    }
+                    if chunk.0.iter().all(|&row| row == 0) {
+                        self.chunks.remove(&chunk_position);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sets all tiles to be non-solid.
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
+}
+
+impl TileMap for LayerGrid<'_, '_> {
+    fn is_solid(&self, layer: Entity, tile: IVec2) -> bool {
+        self.grids.get(layer).is_ok_and(|grid| grid.is_solid(tile))
+    }
+
+    fn is_solid_region(&self, layer: Entity, min: IVec2, max: IVec2, solid: &mut [u64]) {
+        if let Ok(grid) = self.grids.get(layer) {
+            grid.is_solid_region(min, max, solid);
+        }
+    }
+}
+
+// Returns the chunk containing a tile, and the tile's offset within it.
+fn split(tile: IVec2) -> (IVec2, IVec2) {
+    (
+        tile.div_euclid(IVec2::splat(CHUNK_SIZE)),
+        tile.rem_euclid(IVec2::splat(CHUNK_SIZE)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_solid() {
+        let mut grid = TileGrid::new();
+        grid.set_solid(IVec2::new(3, -40), true);
+
+        assert!(grid.is_solid(IVec2::new(3, -40)));
+        assert!(!grid.is_solid(IVec2::new(2, -40)));
+        assert!(!grid.is_solid(IVec2::new(3, -39)));
+        assert_eq!(grid.chunks.len(), 1);
+
+        grid.set_solid(IVec2::new(3, -40), false);
+
+        assert!(!grid.is_solid(IVec2::new(3, -40)));
+        assert!(grid.chunks.is_empty());
+    }
+
+    #[test]
+    fn fill_rect_across_chunks() {
+        let mut grid = TileGrid::new();
+        grid.fill_rect(IVec2::new(-40, -1), IVec2::new(40, 1), true);
+
+        for tile in [
+            IVec2::new(-40, -1),
+            IVec2::new(-33, 0),
+            IVec2::new(-32, 1),
+            IVec2::new(0, 0),
+            IVec2::new(31, -1),
+            IVec2::new(32, 0),
+            IVec2::new(40, 1),
+        ] {
+            assert!(grid.is_solid(tile), "{tile}");
+        }
+        for tile in [
+            IVec2::new(-41, 0),
+            IVec2::new(41, 0),
+            IVec2::new(0, -2),
+            IVec2::new(0, 2),
+        ] {
+            assert!(!grid.is_solid(tile), "{tile}");
+        }
+        assert_eq!(grid.chunks.len(), 8);
+    }
+
+    #[test]
+    fn fill_rect_full_chunk() {
+        let mut grid = TileGrid::new();
+        grid.fill_rect(IVec2::ZERO, IVec2::splat(CHUNK_SIZE - 1), true);
+
+        assert!(
+            grid.chunks[&IVec2::ZERO]
+                .0
+                .iter()
+                .all(|&row| row == u32::MAX)
+        );
+        assert_eq!(grid.chunks.len(), 1);
+    }
+
+    #[test]
+    fn fill_rect_clear() {
+        let mut grid = TileGrid::new();
+        grid.fill_rect(IVec2::new(-10, -10), IVec2::new(10, 10), true);
+        grid.fill_rect(IVec2::new(-10, -10), IVec2::new(10, 0), false);
+
+        assert!(!grid.is_solid(IVec2::new(0, 0)));
+        assert!(grid.is_solid(IVec2::new(0, 1)));
+        assert_eq!(grid.chunks.len(), 2);
+
+        grid.fill_rect(IVec2::new(-10, 1), IVec2::new(10, 10), false);
+
+        assert!(grid.chunks.is_empty());
+    }
+
+    #[test]
+    fn iter() {
+        let mut grid = TileGrid::new();
+        grid.fill_rect(IVec2::new(31, -1), IVec2::new(32, 0), true);
+        grid.set_solid(IVec2::new(-40, 2), true);
+
+        let mut tiles: Vec<_> = grid.iter().collect();
+        tiles.sort_by_key(|tile| (tile.y, tile.x));
+        assert_eq!(
+            tiles,
+            vec![
+                IVec2::new(31, -1),
+                IVec2::new(32, -1),
+                IVec2::new(31, 0),
+                IVec2::new(32, 0),
+                IVec2::new(-40, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let mut old = TileGrid::new();
+        old.fill_rect(IVec2::new(0, 0), IVec2::new(1, 0), true);
+        old.set_solid(IVec2::new(-40, 2), true);
+        let mut new = TileGrid::new();
+        new.fill_rect(IVec2::new(1, 0), IVec2::new(2, 0), true);
+        new.set_solid(IVec2::new(40, 2), true);
+
+        let mut tiles: Vec<_> = old.diff(&new).collect();
+        tiles.sort_by_key(|tile| (tile.y, tile.x));
+        assert_eq!(
+            tiles,
+            vec![
+                IVec2::new(0, 0),
+                IVec2::new(2, 0),
+                IVec2::new(-40, 2),
+                IVec2::new(40, 2),
+            ]
+        );
+        assert_eq!(old.diff(&old).count(), 0);
+    }
+
+    #[test]
+    fn is_solid_region_across_chunks() {
+        let mut grid = TileGrid::new();
+        grid.fill_rect(IVec2::new(-3, -2), IVec2::new(1, 0), true);
+        grid.set_solid(IVec2::new(33, 1), true);
+        grid.set_solid(IVec2::new(-40, 2), true);
+
+        let (min, max) = (IVec2::new(-36, -3), IVec2::new(35, 2));
+        let size = (max - min + 1).as_uvec2();
+        let mut solid = vec![0; (size.x * size.y).div_ceil(64) as usize];
+        grid.is_solid_region(min, max, &mut solid);
+
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let i = ((y - min.y) as u32 * size.x + (x - min.x) as u32) as usize;
+                assert_eq!(
+                    solid[i / 64] & (1 << (i % 64)) != 0,
+                    grid.is_solid(IVec2::new(x, y)),
+                    "{x}, {y}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use std::{error::Error, fmt, io};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, RenderAssetUsages, io::Reader},
+    ecs::system::SystemParam,
+    image::{CompressedImageFormats, ImageSampler, ImageType, TextureAccessError, TextureError},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{TileGrid, TileMap, validate::TileMapChanged};
+
+/// Plugin for loading [`TileImage`] assets from images, and notifying agents when they are reloaded.
+///
+/// The formats which can be loaded depend on the image features enabled for Bevy, such as `png`.
+#[derive(Debug, Default)]
+pub struct TileImagePlugin;
+
+/// An asset storing which tiles are solid, loaded from an image with one pixel per tile.
+///
+/// The bottom-left pixel of the image is the tile at `(0, 0)`, and tiles outside the image are not solid.
+#[derive(Asset, TypePath, Clone, Debug, Default)]
+pub struct TileImage {
+    size: UVec2,
+    grid: TileGrid,
+}
+
+/// The settings used to decide which pixels of an image are solid tiles when loading a [`TileImage`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TileImageSettings {
+    /// Pixels with a luminance below this value, between `0.0` and `1.0`, are solid.
+    pub threshold: f32,
+    /// If set, pixels with exactly this sRGB color are solid, and all others are not, instead of using the
+    /// threshold.
+    pub color_key: Option<[u8; 3]>,
+}
+
+/// A component which sets the [`TileImage`] used for the solid tiles of a [`Layer`](crate::Layer), for use with
+/// [`LayerImage`].
+#[derive(Component, Clone, Debug, Default)]
+pub struct TileImageHandle(pub Handle<TileImage>);
+
+/// A [`TileMap`] which reads the [`TileImage`] of each [`Layer`](crate::Layer).
+///
+/// Layers without a [`TileImageHandle`], or whose image hasn't loaded yet, have no solid tiles.
+#[derive(SystemParam)]
+pub struct LayerImage<'w, 's> {
+    handles: Query<'w, 's, &'static TileImageHandle>,
+    images: Res<'w, Assets<TileImage>>,
+}
+
+/// Loads [`TileImage`] assets from any image format supported by Bevy.
+///
+/// Files with extensions such as `.tiles.png` use this loader by default, so they aren't loaded as textures.
+#[derive(Debug, Default)]
+pub struct TileImageLoader;
+
+/// An error produced when loading a [`TileImage`].
+#[derive(Debug)]
+pub enum TileImageError {
+    /// The image file couldn't be read.
+    Io(io::Error),
+    /// The image couldn't be decoded.
+    Texture(TextureError),
+    /// The colors of the decoded image couldn't be read.
+    TextureAccess(TextureAccessError),
+}
+
+impl Plugin for TileImagePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<TileImage>()
+            .register_asset_loader(TileImageLoader)
+            .add_message::<TileMapChanged>()
+            .add_systems(PreUpdate, reload);
+    }
+}
+
+// Writes a `TileMapChanged` message with the tiles which changed for every layer using an image when it is loaded,
+// reloaded or removed, or when a layer switches to a different image, so agents are moved out of new solid tiles and
+// cached walls are updated.
+//
+// A copy of the tiles of each image is kept, as the previous version of a reloaded image is no longer available.
+fn reload(
+    mut reader: MessageReader<AssetEvent<TileImage>>,
+    images: Res<Assets<TileImage>>,
+    changed_layers: Query<(Entity, &TileImageHandle), Changed<TileImageHandle>>,
+    mut removed_layers: RemovedComponents<TileImageHandle>,
+    mut grids: Local<HashMap<AssetId<TileImage>, TileGrid>>,
+    mut layer_images: Local<HashMap<Entity, AssetId<TileImage>>>,
+    mut writer: MessageWriter<TileMapChanged>,
+) {
+    let empty = TileGrid::new();
+
+    for layer in removed_layers.read() {
+        if let Some(old) = layer_images.remove(&layer) {
+            let old = grids.get(&old).unwrap_or(&empty);
+            write_changes(&mut writer, layer, old, &empty);
+        }
+    }
+
+    for (layer, handle) in &changed_layers {
+        let new = handle.0.id();
+        let old = layer_images.insert(layer, new);
+        if old == Some(new) {
+            continue;
+        }
+
+        let old = old.and_then(|old| grids.get(&old)).unwrap_or(&empty);
+        let new = grids.get(&new).unwrap_or(&empty);
+        write_changes(&mut writer, layer, old, new);
+    }
+
+    for event in reader.read() {
+        let (id, old) = match *event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
+                let Some(image) = images.get(id) else {
+                    continue;
+                };
+                (id, grids.insert(id, image.grid.clone()))
+            }
+            AssetEvent::Removed { id } => (id, grids.remove(&id)),
+            AssetEvent::Unused { .. } | AssetEvent::LoadedWithDependencies { .. } => continue,
+        };
+
+        let old = old.as_ref().unwrap_or(&empty);
+        let new = grids.get(&id).unwrap_or(&empty);
+        for (&layer, _) in layer_images.iter().filter(|(_, image)| **image == id) {
+            write_changes(&mut writer, layer, old, new);
+        }
+    }
+}
+
+fn write_changes(
+    writer: &mut MessageWriter<TileMapChanged>,
+    layer: Entity,
+    old: &TileGrid,
+    new: &TileGrid,
+) {
+    let tiles: Vec<_> = old.diff(new).collect();
+    if !tiles.is_empty() {
+        writer.write(TileMapChanged { layer, tiles });
+    }
+}
+
+impl TileImage {
+    /// Creates a [`TileImage`] from the pixels of an image.
+    pub fn from_image(
+        image: &Image,
+        settings: &TileImageSettings,
+    ) -> Result<Self, TextureAccessError> {
+        let size = image.size();
+        let mut grid = TileGrid::new();
+        for y in 0..size.y {
+            for x in 0..size.x {
+                if settings.is_solid(image.get_color_at(x, y)?) {
+                    // Image rows start from the top, but tile rows start from the bottom.
+                    grid.set_solid(IVec2::new(x as i32, (size.y - 1 - y) as i32), true);
+                }
+            }
+        }
+
+        Ok(TileImage { size, grid })
+    }
+
+    /// Returns the width and height of the image, in tiles.
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    /// Returns `true` if the tile at the given coordinates is solid.
+    pub fn is_solid(&self, tile: IVec2) -> bool {
+        self.grid.is_solid(tile)
+    }
+}
+
+impl TileImageSettings {
+    fn is_solid(&self, color: Color) -> bool {
+        match self.color_key {
+            Some(key) => color.to_srgba().to_u8_array_no_alpha() == key,
+            None => color.luminance() < self.threshold,
+        }
+    }
+}
+
+impl Default for TileImageSettings {
+    fn default() -> Self {
+        TileImageSettings {
+            threshold: 0.5,
+            color_key: None,
+        }
+    }
+}
+
+impl TileMap for LayerImage<'_, '_> {
+    fn is_solid(&self, layer: Entity, tile: IVec2) -> bool {
+        self.handles
+            .get(layer)
+            .ok()
+            .and_then(|handle| self.images.get(&handle.0))
+            .is_some_and(|image| image.is_solid(tile))
+    }
+
+    fn is_solid_region(&self, layer: Entity, min: IVec2, max: IVec2, solid: &mut [u64]) {
+        if let Some(image) = self
+            .handles
+            .get(layer)
+            .ok()
+            .and_then(|handle| self.images.get(&handle.0))
+        {
+            image.grid.is_solid_region(min, max, solid);
+        }
+    }
+}
+
+impl AssetLoader for TileImageLoader {
+    type Asset = TileImage;
+    type Settings = TileImageSettings;
+    type Error = TileImageError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &TileImageSettings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<TileImage, TileImageError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        let extension = load_context
+            .path()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        let image = Image::from_buffer(
+            &bytes,
+            ImageType::Extension(extension),
+            CompressedImageFormats::NONE,
+            true,
+            ImageSampler::Default,
+            RenderAssetUsages::default(),
+        )?;
+
+        Ok(TileImage::from_image(&image, settings)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tiles.png", "tiles.bmp", "tiles.qoi", "tiles.tga"]
+    }
+}
+
+impl fmt::Display for TileImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TileImageError::Io(err) => write!(f, "failed to read tile image: {err}"),
+            TileImageError::Texture(err) => write!(f, "failed to decode tile image: {err}"),
+            TileImageError::TextureAccess(err) => {
+                write!(f, "failed to read tile image colors: {err}")
+            }
+        }
+    }
+}
+
+impl Error for TileImageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TileImageError::Io(err) => Some(err),
+            TileImageError::Texture(err) => Some(err),
+            TileImageError::TextureAccess(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for TileImageError {
+    fn from(err: io::Error) -> Self {
+        TileImageError::Io(err)
+    }
+}
+
+impl From<TextureError> for TileImageError {
+    fn from(err: TextureError) -> Self {
+        TileImageError::Texture(err)
+    }
+}
+
+impl From<TextureAccessError> for TileImageError {
+    fn from(err: TextureAccessError) -> Self {
+        TileImageError::TextureAccess(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::image::ToExtents;
+
+    use super::*;
+
+    fn image(pixels: &[[Color; 3]; 2]) -> Image {
+        let mut image = Image::default();
+        image.resize(UVec2::new(3, 2).to_extents());
+        for (y, row) in pixels.iter().enumerate() {
+            for (x, &color) in row.iter().enumerate() {
+                image.set_color_at(x as u32, y as u32, color).unwrap();
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn reload() {
+        let mut app = App::new();
+        app.add_plugins((
+            TaskPoolPlugin::default(),
+            AssetPlugin::default(),
+            TileImagePlugin,
+        ));
+        let mut images = app.world_mut().resource_mut::<Assets<TileImage>>();
+        let first = images.add(tile_image(&[IVec2::new(0, 0), IVec2::new(1, 0)]));
+        let second = images.add(tile_image(&[IVec2::new(5, 5)]));
+        let layer = app.world_mut().spawn(TileImageHandle(first.clone())).id();
+        let mut cursor = app
+            .world()
+            .resource::<Messages<TileMapChanged>>()
+            .get_cursor();
+        // Asset events are only written at the end of each update, so they are read in the next one.
+        let mut read = |app: &mut App| {
+            app.update();
+            app.update();
+            let messages = app.world().resource::<Messages<TileMapChanged>>();
+            let mut tiles: Vec<_> = cursor
+                .read(messages)
+                .inspect(|message| assert_eq!(message.layer, layer))
+                .flat_map(|message| message.tiles.iter().copied())
+                .collect();
+            tiles.sort_by_key(|tile| (tile.y, tile.x));
+            tiles
+        };
+
+        assert_eq!(read(&mut app), vec![IVec2::new(0, 0), IVec2::new(1, 0)]);
+        assert_eq!(read(&mut app), vec![]);
+
+        let mut images = app.world_mut().resource_mut::<Assets<TileImage>>();
+        let image = images.get_mut(&first).unwrap();
+        image.grid.set_solid(IVec2::new(0, 0), false);
+        image.grid.set_solid(IVec2::new(2, 0), true);
+        assert_eq!(read(&mut app), vec![IVec2::new(0, 0), IVec2::new(2, 0)]);
+
+        app.world_mut()
+            .entity_mut(layer)
+            .insert(TileImageHandle(second));
+        assert_eq!(
+            read(&mut app),
+            vec![IVec2::new(1, 0), IVec2::new(2, 0), IVec2::new(5, 5)]
+        );
+
+        app.world_mut()
+            .entity_mut(layer)
+            .remove::<TileImageHandle>();
+        assert_eq!(read(&mut app), vec![IVec2::new(5, 5)]);
+    }
+
+    fn tile_image(tiles: &[IVec2]) -> TileImage {
+        let mut grid = TileGrid::new();
+        for &tile in tiles {
+            grid.set_solid(tile, true);
+        }
+        TileImage {
+            size: UVec2::splat(8),
+            grid,
+        }
+    }
+
+    #[test]
+    fn from_image_threshold() {
+        let image = image(&[
+            [Color::BLACK, Color::WHITE, Color::WHITE],
+            [
+                Color::srgb(0.2, 0.2, 0.2),
+                Color::WHITE,
+                Color::srgb(0.9, 0.9, 0.9),
+            ],
+        ]);
+        let tiles = TileImage::from_image(&image, &TileImageSettings::default()).unwrap();
+
+        assert_eq!(tiles.size(), UVec2::new(3, 2));
+        assert!(tiles.is_solid(IVec2::new(0, 0)));
+        assert!(tiles.is_solid(IVec2::new(0, 1)));
+        assert!(!tiles.is_solid(IVec2::new(1, 0)));
+        assert!(!tiles.is_solid(IVec2::new(2, 0)));
+        assert!(!tiles.is_solid(IVec2::new(2, 1)));
+        assert!(!tiles.is_solid(IVec2::new(-1, 0)));
+    }
+
+    #[test]
+    fn from_image_color_key() {
+        let image = image(&[
+            [Color::BLACK, Color::srgb_u8(255, 0, 0), Color::WHITE],
+            [Color::WHITE, Color::WHITE, Color::srgb_u8(255, 0, 0)],
+        ]);
+        let settings = TileImageSettings {
+            color_key: Some([255, 0, 0]),
+            ..default()
+        };
+        let tiles = TileImage::from_image(&image, &settings).unwrap();
+
+        assert!(!tiles.is_solid(IVec2::new(0, 1)));
+        assert!(tiles.is_solid(IVec2::new(1, 1)));
+        assert!(tiles.is_solid(IVec2::new(2, 0)));
+        assert!(!tiles.is_solid(IVec2::new(2, 1)));
+    }
+}
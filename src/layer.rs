@@ -6,6 +6,8 @@ use bevy::prelude::*;
 pub struct Layer {
     tile_size: f32,
     scale: f32,
+    collision_iterations: u32,
+    separation: f32,
 }
 
 impl Layer {
@@ -17,14 +19,55 @@ impl Layer {
         Layer {
             tile_size,
             scale: tile_size.recip(),
+            collision_iterations: 4,
+            separation: 0.0,
         }
     }
 
+    /// Sets the maximum number of collisions resolved for each agent in a single simulation step.
+    ///
+    /// After each collision, the agent spends the remainder of the step moving along its corrected velocity,
+    /// allowing it to slide along walls and other agents. If the limit is reached, the agent stops at the point
+    /// of its last collision. Defaults to `4`.
+    pub fn with_collision_iterations(mut self, collision_iterations: u32) -> Self {
+        debug_assert!(
+            collision_iterations > 0,
+            "collision_iterations must be positive"
+        );
+        self.collision_iterations = collision_iterations;
+        self
+    }
+
+    /// Enables pushing apart agents which overlap each other, obstacles, or solid tiles.
+    ///
+    /// Collisions prevent agents from moving further into each other, but agents which already overlap, for example
+    /// because they were spawned or teleported on top of each other, are not otherwise moved apart. The separation
+    /// strength is the fraction of the overlap resolved at the end of each simulation step, between `0` and `1`.
+    /// Defaults to `0`, which disables separation.
+    pub fn with_separation(mut self, separation: f32) -> Self {
+        debug_assert!(
+            (0.0..=1.0).contains(&separation),
+            "separation must be between 0 and 1"
+        );
+        self.separation = separation;
+        self
+    }
+
     /// Returns the tile size of this [`Layer`].
     pub fn tile_size(&self) -> f32 {
         self.tile_size
     }
 
+    /// Returns the maximum number of collisions resolved for each agent in a single simulation step.
+    pub fn collision_iterations(&self) -> u32 {
+        self.collision_iterations
+    }
+
+    /// Returns the fraction of the overlap between agents resolved in each simulation step.
+    pub fn separation(&self) -> f32 {
+        self.separation
+    }
+
     pub(crate) fn scale(&self) -> f32 {
         self.scale
     }
@@ -24,12 +24,25 @@ pub(crate) enum InterpolationState {
     },
 }
 
+impl InterpolationState {
+    // Returns the physical position of an agent, which differs from its transform while it is interpolated.
+    #[cfg(feature = "scene")]
+    pub(crate) fn position(&self, transform: Ref<Transform>) -> Vec2 {
+        match *self {
+            InterpolationState::Interpolated {
+                end, change_tick, ..
+            } if transform.last_changed() == change_tick => end,
+            _ => transform.translation.xy(),
+        }
+    }
+}
+
 pub(crate) fn update_fixed(mut agents: Query<(&mut Transform, &mut InterpolationState)>) {
     agents
         .par_iter_mut()
         .for_each(|(mut transform, mut state)| {
             match *state {
-                InterpolationState::Fixed { .. } => return,
+                InterpolationState::Fixed { .. } => {}
                 InterpolationState::Interpolated {
                     end, change_tick, ..
                 } if transform.last_changed() == change_tick => {
@@ -4,10 +4,24 @@
 pub mod diagnostic;
 
 mod agent;
+mod cache;
+mod cast;
 mod collision;
+#[cfg(feature = "debug")]
+mod debug;
+mod grid;
+#[cfg(feature = "image")]
+mod image;
 mod layer;
 mod lerp;
+mod obstacle;
+mod query;
+#[cfg(feature = "scene")]
+mod scene;
+mod sensor;
+mod separation;
 mod tile;
+mod validate;
 
 use std::marker::PhantomData;
 
@@ -16,12 +30,32 @@ use bevy::{
     prelude::*,
 };
 
-use crate::tile::{TileChanged, TileIndex};
+use crate::{
+    obstacle::{ObstacleIndex, ObstacleRemoved},
+    tile::{TileChanged, TileIndex},
+};
 
+#[cfg(feature = "debug")]
+pub use self::debug::{JostleDebug, JostleDebugPlugin};
+#[cfg(feature = "image")]
+pub use self::image::{
+    LayerImage, TileImage, TileImageError, TileImageHandle, TileImageLoader, TileImagePlugin,
+    TileImageSettings,
+};
+#[cfg(feature = "scene")]
+pub use self::scene::{AgentScene, JostleScene, LayerScene, ObstacleScene, SceneError};
 pub use self::{
-    agent::{Agent, Velocity},
+    agent::{Agent, CollisionGroups, Mass, Velocity},
+    cache::TileCache,
+    cast::{CastHit, CastQuery},
+    collision::{AgentCollided, CollisionTarget},
+    grid::{LayerGrid, TileGrid},
     layer::Layer,
-    tile::TileMap,
+    obstacle::Obstacle,
+    query::AgentQuery,
+    sensor::{Sensor, SensorEntered, SensorExited},
+    tile::{TileMap, TileShape},
+    validate::TileMapChanged,
 };
 
 /// Plugin for adding [`jostle`](crate) functionality to an app.
@@ -66,7 +100,13 @@ where
 {
     fn build(&self, app: &mut App) {
         app.init_resource::<TileIndex>()
-            .add_message::<TileChanged>();
+            .init_resource::<ObstacleIndex>()
+            .add_message::<TileChanged>()
+            .add_message::<ObstacleRemoved>()
+            .add_message::<TileMapChanged>()
+            .add_message::<AgentCollided>()
+            .add_message::<SensorEntered>()
+            .add_message::<SensorExited>();
 
         app.add_systems(
             FixedFirst,
@@ -76,9 +116,14 @@ where
         app.add_systems(
             self.schedule,
             (
+                measure!(diagnostic::REVALIDATE_AGENTS, validate::revalidate::<T>),
                 measure!(diagnostic::UPDATE_AGENT_TILE, agent::update_tile),
+                measure!(diagnostic::UPDATE_TILE_CACHE, cache::update::<T>),
                 measure!(diagnostic::UPDATE_TILE_INDEX, tile::update_index),
+                measure!(diagnostic::UPDATE_OBSTACLE_INDEX, obstacle::update_index),
+                measure!(diagnostic::UPDATE_SENSORS, sensor::update),
                 measure!(diagnostic::PROCESS_COLLISIONS, collision::process::<T>),
+                measure!(diagnostic::SEPARATE_AGENTS, separation::separate::<T>),
             )
                 .chain_ignore_deferred()
                 .in_set(JostleSystems),
@@ -91,7 +136,10 @@ where
         );
 
         #[cfg(feature = "diagnostic")]
-        diagnostic::register(app);
+        {
+            app.add_systems(self.schedule, diagnostic::count.after(JostleSystems));
+            diagnostic::register(app);
+        }
     }
 }
 
@@ -0,0 +1,301 @@
+use bevy::{
+    ecs::{lifecycle::HookContext, relationship::Relationship, world::DeferredWorld},
+    platform::collections::{HashMap, hash_map},
+    prelude::*,
+};
+use smallvec::SmallVec;
+
+use crate::{Layer, tile::Tile};
+
+/// Component for immovable circular obstacles in the simulation, such as trees or pillars.
+///
+/// Obstacles block agents like an [`Agent`](crate::Agent) with zero velocity, but are stored in a separate index which
+/// is only updated when the obstacle's [`Transform`] changes. Agents which don't collide with walls, as determined by
+/// their [`CollisionGroups`](crate::CollisionGroups), also pass through obstacles.
+#[derive(Component, Clone, Copy, Debug)]
+#[require(Transform, ObstacleState)]
+pub struct Obstacle {
+    radius: f32,
+}
+
+#[derive(Component, Clone, Copy, Debug, Default)]
+#[component(on_replace = ObstacleState::on_replace)]
+pub(crate) struct ObstacleState {
+    pub(crate) position: Vec2,
+    pub(crate) bounds: Option<ObstacleBounds>,
+}
+
+// The rectangle of tiles overlapped by an obstacle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ObstacleBounds {
+    pub(crate) layer: Entity,
+    pub(crate) min: IVec2,
+    pub(crate) max: IVec2,
+}
+
+#[derive(Resource, Default, Debug)]
+pub(crate) struct ObstacleIndex {
+    index: HashMap<Tile, SmallVec<[Entity; 2]>>,
+}
+
+#[derive(Clone, Debug, Message, PartialEq, Eq)]
+pub(crate) struct ObstacleRemoved {
+    obstacle: Entity,
+    bounds: ObstacleBounds,
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_index(
+    mut index: ResMut<ObstacleIndex>,
+    mut removed: MessageReader<ObstacleRemoved>,
+    mut removed_parents: RemovedComponents<ChildOf>,
+    layers: Query<&Layer>,
+    mut obstacles: Query<
+        (Entity, &Obstacle, &Transform, &mut ObstacleState, &ChildOf),
+        Or<(Changed<Obstacle>, Changed<Transform>, Changed<ChildOf>)>,
+    >,
+    mut unparented: Query<&mut ObstacleState, Without<ChildOf>>,
+) {
+    for event in removed.read() {
+        index.remove(event.obstacle, event.bounds);
+    }
+
+    // Obstacles which are no longer in a layer are removed from the index. Despawning the layer also despawns its
+    // obstacles, which are then removed by `ObstacleState::on_replace`.
+    for id in removed_parents.read() {
+        if let Ok(mut state) = unparented.get_mut(id)
+            && let Some(old) = state.bounds.take()
+        {
+            index.remove(id, old);
+        }
+    }
+
+    for (id, obstacle, transform, mut state, parent) in &mut obstacles {
+        let position = transform.translation.xy();
+        let layer_id = parent.get();
+        let bounds = layers.get(layer_id).ok().map(|layer| ObstacleBounds {
+            layer: layer_id,
+            min: Tile::floor(layer_id, position - obstacle.radius, layer.scale()).tile(),
+            max: Tile::floor(layer_id, position + obstacle.radius, layer.scale()).tile(),
+        });
+
+        if state.position == position && state.bounds == bounds {
+            continue;
+        }
+
+        if state.bounds != bounds {
+            if let Some(old) = state.bounds {
+                index.remove(id, old);
+            }
+            if let Some(new) = bounds {
+                index.insert(id, new);
+            }
+        }
+
+        state.position = position;
+        state.bounds = bounds;
+    }
+}
+
+impl Obstacle {
+    pub fn new(radius: f32) -> Self {
+        Obstacle { radius }
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+}
+
+impl ObstacleState {
+    fn on_replace(mut world: DeferredWorld, context: HookContext) {
+        let state = world.entity(context.entity).get::<ObstacleState>().unwrap();
+        if let Some(bounds) = state.bounds {
+            world.write_message(ObstacleRemoved {
+                obstacle: context.entity,
+                bounds,
+            });
+        }
+    }
+}
+
+impl ObstacleIndex {
+    fn insert(&mut self, id: Entity, bounds: ObstacleBounds) {
+        for tile in Tile::rect(bounds.layer, bounds.min, bounds.max) {
+            self.index.entry(tile).or_default().push(id);
+        }
+    }
+
+    fn remove(&mut self, id: Entity, bounds: ObstacleBounds) {
+        for tile in Tile::rect(bounds.layer, bounds.min, bounds.max) {
+            if let hash_map::Entry::Occupied(mut entry) = self.index.entry(tile) {
+                let obstacles = entry.get_mut();
+                if let Some(pos) = obstacles.iter().position(|&o| o == id) {
+                    obstacles.swap_remove(pos);
+                }
+                if obstacles.is_empty() {
+                    entry.remove();
+                }
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, tile: Tile) -> &[Entity] {
+        match self.index.get(&tile) {
+            Some(obstacles) => obstacles,
+            None => &[],
+        }
+    }
+
+    // Returns each obstacle indexed in the tiles of a layer from `min` to `max`.
+    //
+    // Obstacles are indexed in every tile they overlap, so each is only returned from the first queried tile it
+    // overlaps, so it is returned at most once.
+    pub(crate) fn query<'a>(
+        &'a self,
+        obstacles: &'a Query<(&Obstacle, &ObstacleState)>,
+        layer: Entity,
+        min: IVec2,
+        max: IVec2,
+    ) -> impl Iterator<Item = (Entity, &'a Obstacle, &'a ObstacleState)> {
+        Tile::rect(layer, min, max).flat_map(move |tile| {
+            self.get(tile).iter().filter_map(move |&id| {
+                let (obstacle, state) = obstacles.get(id).ok()?;
+                let bounds = state.bounds?;
+                (bounds.min.max(min) == tile.tile()).then_some((id, obstacle, state))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn obstacle_spawned() {
+        let mut world = make_world();
+        let layer = world.spawn(Layer::default()).id();
+        let obstacle = world
+            .spawn((
+                Obstacle::new(0.7),
+                Transform::from_xyz(0.5, 0.5, 0.0),
+                ChildOf(layer),
+            ))
+            .id();
+        world.run_system_cached(update_index).unwrap();
+
+        assert_indexed(
+            &world,
+            layer,
+            obstacle,
+            IVec2::new(-1, -1),
+            IVec2::new(1, 1),
+        );
+    }
+
+    #[test]
+    fn obstacle_moved() {
+        let mut world = make_world();
+        let layer = world.spawn(Layer::default()).id();
+        let obstacle = world
+            .spawn((
+                Obstacle::new(0.2),
+                Transform::from_xyz(0.5, 0.5, 0.0),
+                ChildOf(layer),
+            ))
+            .id();
+        world.run_system_cached(update_index).unwrap();
+
+        world.get_mut::<Transform>(obstacle).unwrap().translation = Vec3::new(2.1, 0.5, 0.0);
+        world.run_system_cached(update_index).unwrap();
+
+        assert_indexed(&world, layer, obstacle, IVec2::new(1, 0), IVec2::new(2, 0));
+    }
+
+    #[test]
+    fn obstacle_despawned() {
+        let mut world = make_world();
+        let layer = world.spawn(Layer::default()).id();
+        let obstacle = world
+            .spawn((
+                Obstacle::new(0.7),
+                Transform::from_xyz(0.5, 0.5, 0.0),
+                ChildOf(layer),
+            ))
+            .id();
+        world.run_system_cached(update_index).unwrap();
+
+        world.despawn(obstacle);
+        world.run_system_cached(update_index).unwrap();
+
+        assert!(world.resource::<ObstacleIndex>().index.is_empty());
+    }
+
+    #[test]
+    fn obstacle_parent_removed() {
+        let mut world = make_world();
+        let layer = world.spawn(Layer::default()).id();
+        let obstacle = world
+            .spawn((
+                Obstacle::new(0.7),
+                Transform::from_xyz(0.5, 0.5, 0.0),
+                ChildOf(layer),
+            ))
+            .id();
+        world.run_system_cached(update_index).unwrap();
+
+        world.entity_mut(obstacle).remove::<ChildOf>();
+        world.run_system_cached(update_index).unwrap();
+
+        assert!(world.resource::<ObstacleIndex>().index.is_empty());
+
+        world.entity_mut(obstacle).insert(ChildOf(layer));
+        world.run_system_cached(update_index).unwrap();
+
+        assert_indexed(
+            &world,
+            layer,
+            obstacle,
+            IVec2::new(-1, -1),
+            IVec2::new(1, 1),
+        );
+    }
+
+    #[test]
+    fn obstacle_layer_despawned() {
+        let mut world = make_world();
+        let layer = world.spawn(Layer::default()).id();
+        world.spawn((
+            Obstacle::new(0.7),
+            Transform::from_xyz(0.5, 0.5, 0.0),
+            ChildOf(layer),
+        ));
+        world.run_system_cached(update_index).unwrap();
+
+        world.despawn(layer);
+        world.run_system_cached(update_index).unwrap();
+
+        assert!(world.resource::<ObstacleIndex>().index.is_empty());
+    }
+
+    fn make_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<ObstacleIndex>();
+        world.init_resource::<Messages<ObstacleRemoved>>();
+        world
+    }
+
+    fn assert_indexed(world: &World, layer: Entity, obstacle: Entity, min: IVec2, max: IVec2) {
+        let index = world.resource::<ObstacleIndex>();
+        for tile in Tile::rect(layer, min, max) {
+            assert_eq!(index.get(tile), &[obstacle], "tile {tile:?}");
+        }
+        assert_eq!(
+            index.index.len(),
+            ((max.x - min.x + 1) * (max.y - min.y + 1)) as usize
+        );
+    }
+}
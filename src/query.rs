@@ -0,0 +1,151 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    Agent, Layer,
+    agent::AgentState,
+    tile::{Tile, TileIndex},
+};
+
+/// A system parameter for finding agents by their position within a [`Layer`].
+///
+/// Positions are those used by the most recent simulation step, so agents moved since then
+/// (for example by the collision system in the same step) are reported at their previous position.
+#[derive(SystemParam)]
+pub struct AgentQuery<'w, 's> {
+    index: Res<'w, TileIndex>,
+    layers: Query<'w, 's, &'static Layer>,
+    agents: Query<'w, 's, (&'static Agent, &'static AgentState)>,
+}
+
+impl AgentQuery<'_, '_> {
+    /// Returns all agents in the given layer whose circle intersects the circle with the given center and radius.
+    ///
+    /// Each agent is returned at most once, in no particular order.
+    pub fn intersecting(
+        &self,
+        layer: Entity,
+        center: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = Entity> {
+        let (min, max) = match self.layers.get(layer) {
+            Ok(layer_data) => (
+                Tile::floor(layer, center - radius, layer_data.scale()).tile(),
+                Tile::floor(layer, center + radius, layer_data.scale()).tile(),
+            ),
+            Err(_) => (IVec2::ONE, IVec2::ZERO),
+        };
+
+        self.index
+            .query(layer, min, max, move |id| {
+                let (agent, state) = self.agents.get(id).ok()?;
+                Some(((agent, state), state.tile?.tile()))
+            })
+            .filter_map(move |(id, (agent, state))| {
+                let combined_radius = radius + agent.radius();
+                (state.position.distance_squared(center) <= combined_radius * combined_radius)
+                    .then_some(id)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::SystemState, prelude::*};
+
+    use crate::{
+        agent::update_tile,
+        tile::{TileChanged, update_index},
+    };
+
+    use super::*;
+
+    #[test]
+    fn intersecting_single_tile() {
+        let mut world = make_world();
+        let layer = world.spawn(Layer::default()).id();
+        let near = spawn_agent(&mut world, layer, Vec2::new(0.5, 0.5), 0.2);
+        let overlapping = spawn_agent(&mut world, layer, Vec2::new(1.1, 0.5), 0.2);
+        let _far = spawn_agent(&mut world, layer, Vec2::new(1.5, 1.5), 0.2);
+        update(&mut world);
+
+        let agents = query(&mut world, layer, Vec2::new(0.5, 0.5), 0.5);
+
+        assert_eq!(agents, sorted(vec![near, overlapping]));
+    }
+
+    #[test]
+    fn intersecting_large_radius() {
+        let mut world = make_world();
+        let layer = world.spawn(Layer::default()).id();
+        let inside = spawn_agent(&mut world, layer, Vec2::new(3.5, -2.5), 0.2);
+        let edge = spawn_agent(&mut world, layer, Vec2::new(-4.9, 0.0), 0.2);
+        let _outside = spawn_agent(&mut world, layer, Vec2::new(4.0, 4.0), 0.2);
+        let _other_layer = {
+            let other = world.spawn(Layer::default()).id();
+            spawn_agent(&mut world, other, Vec2::ZERO, 0.2)
+        };
+        update(&mut world);
+
+        let agents = query(&mut world, layer, Vec2::ZERO, 5.0);
+
+        assert_eq!(agents, sorted(vec![inside, edge]));
+    }
+
+    #[test]
+    fn intersecting_custom_tile_size() {
+        let mut world = make_world();
+        let layer = world.spawn(Layer::new(0.25)).id();
+        let inside = spawn_agent(&mut world, layer, Vec2::new(0.9, 0.0), 0.1);
+        let _outside = spawn_agent(&mut world, layer, Vec2::new(1.2, 0.0), 0.1);
+        update(&mut world);
+
+        let agents = query(&mut world, layer, Vec2::ZERO, 1.0);
+
+        assert_eq!(agents, vec![inside]);
+    }
+
+    #[test]
+    fn intersecting_missing_layer() {
+        let mut world = make_world();
+        let layer = world.spawn(Layer::default()).id();
+        spawn_agent(&mut world, layer, Vec2::ZERO, 0.2);
+        update(&mut world);
+
+        let agents = query(&mut world, Entity::PLACEHOLDER, Vec2::ZERO, 1.0);
+
+        assert!(agents.is_empty());
+    }
+
+    fn make_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<Messages<TileChanged>>();
+        world.init_resource::<TileIndex>();
+        world
+    }
+
+    fn spawn_agent(world: &mut World, layer: Entity, position: Vec2, radius: f32) -> Entity {
+        world
+            .spawn((
+                Agent::new(radius),
+                Transform::from_translation(position.extend(0.0)),
+                ChildOf(layer),
+            ))
+            .id()
+    }
+
+    fn update(world: &mut World) {
+        world.run_system_cached(update_tile).unwrap();
+        world.run_system_cached(update_index).unwrap();
+    }
+
+    fn query(world: &mut World, layer: Entity, center: Vec2, radius: f32) -> Vec<Entity> {
+        let mut state = SystemState::<AgentQuery>::new(world);
+        let query = state.get(world);
+        sorted(query.intersecting(layer, center, radius).collect())
+    }
+
+    fn sorted(mut agents: Vec<Entity>) -> Vec<Entity> {
+        agents.sort();
+        agents
+    }
+}
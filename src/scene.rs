@@ -0,0 +1,481 @@
+use std::{error::Error, fmt};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Agent, CollisionGroups, Layer, Mass, Obstacle, Sensor, TileGrid, Velocity,
+    lerp::InterpolationState,
+};
+
+/// A complete simulation scenario, which can be saved to and loaded from RON, for example to reproduce a bug.
+///
+/// Solid tiles are stored in a [`TileGrid`] on each layer, so scenes should be simulated with
+/// [`LayerGrid`](crate::LayerGrid).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct JostleScene {
+    /// The layers of the scene.
+    pub layers: Vec<LayerScene>,
+}
+
+/// A [`Layer`] in a [`JostleScene`], with its solid tiles and agents.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayerScene {
+    /// The tile size of the layer.
+    pub tile_size: f32,
+    /// The maximum number of collisions resolved for each agent in a single step, see
+    /// [`Layer::with_collision_iterations`].
+    #[serde(default = "default_collision_iterations")]
+    pub collision_iterations: u32,
+    /// The fraction of overlaps resolved in each step, see [`Layer::with_separation`].
+    #[serde(default)]
+    pub separation: f32,
+    /// The coordinates of each solid tile.
+    #[serde(default)]
+    pub solid: Vec<IVec2>,
+    /// The agents in the layer.
+    #[serde(default)]
+    pub agents: Vec<AgentScene>,
+    /// The obstacles in the layer.
+    #[serde(default)]
+    pub obstacles: Vec<ObstacleScene>,
+}
+
+/// An [`Agent`] in a [`JostleScene`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AgentScene {
+    /// The radius of the agent.
+    pub radius: f32,
+    /// The position of the agent within its layer.
+    pub position: Vec2,
+    /// The velocity of the agent.
+    #[serde(default)]
+    pub velocity: Vec2,
+    /// The mass of the agent, if it can push and be pushed by other agents.
+    #[serde(default)]
+    pub mass: Option<f32>,
+    /// The collision groups of the agent.
+    #[serde(default)]
+    pub collision_groups: CollisionGroups,
+    /// Whether the agent is a [`Sensor`].
+    #[serde(default)]
+    pub sensor: bool,
+}
+
+/// An [`Obstacle`] in a [`JostleScene`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObstacleScene {
+    /// The radius of the obstacle.
+    pub radius: f32,
+    /// The position of the obstacle within its layer.
+    pub position: Vec2,
+}
+
+/// An error produced when spawning a [`JostleScene`] with invalid values.
+///
+/// Layers are identified by their index in [`JostleScene::layers`], and agents and obstacles by their index within
+/// their layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneError {
+    /// The tile size of a layer isn't positive and finite.
+    TileSize {
+        /// The index of the layer.
+        layer: usize,
+    },
+    /// The collision iterations of a layer are zero.
+    CollisionIterations {
+        /// The index of the layer.
+        layer: usize,
+    },
+    /// The separation of a layer isn't between `0` and `1`.
+    Separation {
+        /// The index of the layer.
+        layer: usize,
+    },
+    /// The radius of an agent isn't positive and finite.
+    AgentRadius {
+        /// The index of the agent's layer.
+        layer: usize,
+        /// The index of the agent.
+        agent: usize,
+    },
+    /// The mass of an agent isn't positive and finite.
+    Mass {
+        /// The index of the agent's layer.
+        layer: usize,
+        /// The index of the agent.
+        agent: usize,
+    },
+    /// The radius of an obstacle isn't positive and finite.
+    ObstacleRadius {
+        /// The index of the obstacle's layer.
+        layer: usize,
+        /// The index of the obstacle.
+        obstacle: usize,
+    },
+}
+
+impl JostleScene {
+    /// Parses a scene from a RON string.
+    pub fn from_ron(ron: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(ron)
+    }
+
+    /// Writes the scene to a RON string.
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    /// Records the layers in a world, with the solid tiles of their [`TileGrid`] and the agents and obstacles which are
+    /// their children.
+    ///
+    /// Agents are recorded at their position after the last simulation step, rather than their interpolated render
+    /// position. Layers, agents and obstacles are ordered by entity index, and tiles by their coordinates, so the same
+    /// world always produces the same scene.
+    pub fn from_world(world: &mut World) -> Self {
+        let mut layers: Vec<_> = world
+            .query::<(Entity, &Layer, Option<&TileGrid>)>()
+            .iter(world)
+            .map(|(id, layer, grid)| {
+                let mut solid: Vec<_> = grid.into_iter().flat_map(|grid| grid.iter()).collect();
+                solid.sort_by_key(|tile| (tile.y, tile.x));
+                (
+                    id,
+                    LayerScene {
+                        tile_size: layer.tile_size(),
+                        collision_iterations: layer.collision_iterations(),
+                        separation: layer.separation(),
+                        solid,
+                        agents: Vec::new(),
+                        obstacles: Vec::new(),
+                    },
+                )
+            })
+            .collect();
+        layers.sort_by_key(|&(id, _)| id.index());
+
+        let mut agents: Vec<_> = world
+            .query::<(
+                Entity,
+                &Agent,
+                Ref<Transform>,
+                &InterpolationState,
+                &Velocity,
+                Option<&Mass>,
+                Has<Sensor>,
+                &ChildOf,
+            )>()
+            .iter(world)
+            .collect();
+        agents.sort_by_key(|&(id, ..)| id.index());
+        for (_, agent, transform, interpolation, velocity, mass, sensor, parent) in agents {
+            if let Some(layer) = child_layer(&mut layers, parent) {
+                layer.agents.push(AgentScene {
+                    radius: agent.radius(),
+                    position: interpolation.position(transform),
+                    velocity: velocity.0,
+                    mass: mass.map(|mass| mass.0),
+                    collision_groups: agent.collision_groups(),
+                    sensor,
+                });
+            }
+        }
+
+        let mut obstacles: Vec<_> = world
+            .query::<(Entity, &Obstacle, &Transform, &ChildOf)>()
+            .iter(world)
+            .collect();
+        obstacles.sort_by_key(|&(id, ..)| id.index());
+        for (_, obstacle, transform, parent) in obstacles {
+            if let Some(layer) = child_layer(&mut layers, parent) {
+                layer.obstacles.push(ObstacleScene {
+                    radius: obstacle.radius(),
+                    position: transform.translation.xy(),
+                });
+            }
+        }
+
+        JostleScene {
+            layers: layers.into_iter().map(|(_, layer)| layer).collect(),
+        }
+    }
+
+    /// Spawns the layers and agents of the scene into a world, returning the entity of each layer.
+    ///
+    /// Nothing is spawned if the scene has invalid values, such as a negative radius, which is returned as an error.
+    pub fn spawn(&self, world: &mut World) -> Result<Vec<Entity>, SceneError> {
+        self.validate()?;
+
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| {
+                let mut grid = TileGrid::new();
+                for &tile in &layer.solid {
+                    grid.set_solid(tile, true);
+                }
+
+                let id = world
+                    .spawn((
+                        Layer::new(layer.tile_size)
+                            .with_collision_iterations(layer.collision_iterations)
+                            .with_separation(layer.separation),
+                        grid,
+                    ))
+                    .id();
+                for agent in &layer.agents {
+                    let mut entity = world.spawn((
+                        Agent::new(agent.radius).with_collision_groups(agent.collision_groups),
+                        Transform::from_translation(agent.position.extend(0.0)),
+                        Velocity(agent.velocity),
+                        ChildOf(id),
+                    ));
+                    if let Some(mass) = agent.mass {
+                        entity.insert(Mass::new(mass));
+                    }
+                    if agent.sensor {
+                        entity.insert(Sensor);
+                    }
+                }
+                for obstacle in &layer.obstacles {
+                    world.spawn((
+                        Obstacle::new(obstacle.radius),
+                        Transform::from_translation(obstacle.position.extend(0.0)),
+                        ChildOf(id),
+                    ));
+                }
+                id
+            })
+            .collect();
+        Ok(layers)
+    }
+
+    /// Checks that the sizes, radii and masses in the scene are positive and finite, its collision iterations are
+    /// positive, and its separations are between `0` and `1`, as [`JostleScene::spawn`] does before spawning anything.
+    pub fn validate(&self) -> Result<(), SceneError> {
+        let positive = |value: f32| value > 0.0 && value.is_finite();
+        for (index, layer) in self.layers.iter().enumerate() {
+            if !positive(layer.tile_size) {
+                return Err(SceneError::TileSize { layer: index });
+            }
+            if layer.collision_iterations == 0 {
+                return Err(SceneError::CollisionIterations { layer: index });
+            }
+            if !(0.0..=1.0).contains(&layer.separation) {
+                return Err(SceneError::Separation { layer: index });
+            }
+
+            for (agent, scene) in layer.agents.iter().enumerate() {
+                if !positive(scene.radius) {
+                    return Err(SceneError::AgentRadius {
+                        layer: index,
+                        agent,
+                    });
+                }
+                if scene.mass.is_some_and(|mass| !positive(mass)) {
+                    return Err(SceneError::Mass {
+                        layer: index,
+                        agent,
+                    });
+                }
+            }
+            for (obstacle, scene) in layer.obstacles.iter().enumerate() {
+                if !positive(scene.radius) {
+                    return Err(SceneError::ObstacleRadius {
+                        layer: index,
+                        obstacle,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::TileSize { layer } => {
+                write!(f, "tile size of layer {layer} must be positive")
+            }
+            SceneError::CollisionIterations { layer } => {
+                write!(f, "collision iterations of layer {layer} must be positive")
+            }
+            SceneError::Separation { layer } => {
+                write!(f, "separation of layer {layer} must be between 0 and 1")
+            }
+            SceneError::AgentRadius { layer, agent } => {
+                write!(
+                    f,
+                    "radius of agent {agent} in layer {layer} must be positive"
+                )
+            }
+            SceneError::Mass { layer, agent } => {
+                write!(f, "mass of agent {agent} in layer {layer} must be positive")
+            }
+            SceneError::ObstacleRadius { layer, obstacle } => {
+                write!(
+                    f,
+                    "radius of obstacle {obstacle} in layer {layer} must be positive"
+                )
+            }
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+fn default_collision_iterations() -> u32 {
+    Layer::default().collision_iterations()
+}
+
+// Returns the layer an agent or obstacle is a child of.
+fn child_layer<'a>(
+    layers: &'a mut [(Entity, LayerScene)],
+    parent: &ChildOf,
+) -> Option<&'a mut LayerScene> {
+    let index = layers
+        .binary_search_by_key(&parent.0.index(), |&(id, _)| id.index())
+        .ok()?;
+    Some(&mut layers[index].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = r#"(
+        layers: [
+            (
+                tile_size: 2.0,
+                separation: 0.5,
+                solid: [(0, 0), (1, 0)],
+                agents: [
+                    (radius: 0.4, position: (1.0, 3.0), velocity: (0.0, -1.0)),
+                    (radius: 0.2, position: (3.0, 3.0), mass: Some(2.0)),
+                    (
+                        radius: 1.0,
+                        position: (5.0, 3.0),
+                        collision_groups: (memberships: 1, filters: 2),
+                        sensor: true,
+                    ),
+                ],
+                obstacles: [
+                    (radius: 0.5, position: (1.0, 7.0)),
+                ],
+            ),
+        ],
+    )"#;
+
+    #[test]
+    fn from_ron() {
+        let scene = JostleScene::from_ron(SCENE).unwrap();
+
+        let layer = &scene.layers[0];
+        assert_eq!(layer.tile_size, 2.0);
+        assert_eq!(layer.collision_iterations, 4);
+        assert_eq!(layer.solid, vec![IVec2::new(0, 0), IVec2::new(1, 0)]);
+        assert_eq!(
+            layer.agents[1],
+            AgentScene {
+                radius: 0.2,
+                position: Vec2::new(3.0, 3.0),
+                velocity: Vec2::ZERO,
+                mass: Some(2.0),
+                collision_groups: CollisionGroups::default(),
+                sensor: false,
+            }
+        );
+        assert_eq!(layer.agents[2].collision_groups, CollisionGroups::new(1, 2));
+        assert!(layer.agents[2].sensor);
+        assert_eq!(
+            layer.obstacles,
+            vec![ObstacleScene {
+                radius: 0.5,
+                position: Vec2::new(1.0, 7.0),
+            }]
+        );
+    }
+
+    #[test]
+    fn spawn_from_world() {
+        let scene = JostleScene::from_ron(SCENE).unwrap();
+
+        let mut world = World::new();
+        let layers = scene.spawn(&mut world).unwrap();
+        assert_eq!(layers.len(), 1);
+        assert!(
+            world
+                .get::<TileGrid>(layers[0])
+                .unwrap()
+                .is_solid(IVec2::new(1, 0))
+        );
+
+        let exported = JostleScene::from_world(&mut world);
+        assert_eq!(exported, scene);
+        assert_eq!(
+            JostleScene::from_ron(&exported.to_ron().unwrap()).unwrap(),
+            scene
+        );
+    }
+
+    #[test]
+    fn spawn_invalid() {
+        let mut scene = JostleScene::from_ron(SCENE).unwrap();
+        scene.layers[0].agents[1].mass = Some(-1.0);
+
+        let mut world = World::new();
+        assert_eq!(
+            scene.spawn(&mut world),
+            Err(SceneError::Mass { layer: 0, agent: 1 })
+        );
+        assert_eq!(world.entities().len(), 0);
+
+        scene.layers[0].agents[1].mass = None;
+        scene.layers[0].obstacles[0].radius = f32::NAN;
+        assert_eq!(
+            scene.validate(),
+            Err(SceneError::ObstacleRadius {
+                layer: 0,
+                obstacle: 0
+            })
+        );
+
+        scene.layers[0].separation = 1.5;
+        assert_eq!(scene.validate(), Err(SceneError::Separation { layer: 0 }));
+    }
+
+    #[test]
+    fn from_world_interpolated() {
+        let mut world = World::new();
+        let layer = world.spawn(Layer::default()).id();
+        let agent = world
+            .spawn((
+                Agent::new(0.5),
+                Transform::from_xyz(1.0, 0.0, 0.0),
+                ChildOf(layer),
+            ))
+            .id();
+        let change_tick = world
+            .entity(agent)
+            .get_ref::<Transform>()
+            .unwrap()
+            .last_changed();
+        world
+            .entity_mut(agent)
+            .insert(InterpolationState::Interpolated {
+                start: Vec2::new(0.0, 0.0),
+                end: Vec2::new(2.0, 0.0),
+                change_tick,
+            });
+
+        let scene = JostleScene::from_world(&mut world);
+        assert_eq!(scene.layers[0].agents[0].position, Vec2::new(2.0, 0.0));
+
+        world.increment_change_tick();
+        world.get_mut::<Transform>(agent).unwrap().translation = Vec3::new(5.0, 0.0, 0.0);
+
+        let scene = JostleScene::from_world(&mut world);
+        assert_eq!(scene.layers[0].agents[0].position, Vec2::new(5.0, 0.0));
+    }
+}
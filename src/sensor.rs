@@ -0,0 +1,114 @@
+use bevy::{prelude::*, utils::Parallel};
+
+use crate::{Agent, agent::AgentState, query::AgentQuery};
+
+/// Marker component for agents which detect overlapping agents without blocking them.
+///
+/// A sensor is added alongside an [`Agent`], which determines its radius and collision groups. It moves through other
+/// agents freely, and other agents move through it. A [`SensorEntered`] message is written when an agent starts
+/// overlapping the sensor, and a [`SensorExited`] message when it stops overlapping, or is despawned. Only agents
+/// whose collision groups would collide with the sensor are detected, and sensors never detect each other.
+#[derive(Component, Clone, Copy, Debug, Default)]
+#[require(SensorState)]
+pub struct Sensor;
+
+/// A message written when an [`Agent`] starts overlapping a [`Sensor`].
+#[derive(Clone, Copy, Debug, Message, PartialEq, Eq)]
+pub struct SensorEntered {
+    /// The sensor.
+    pub sensor: Entity,
+    /// The agent which started overlapping the sensor.
+    pub agent: Entity,
+}
+
+/// A message written when an [`Agent`] stops overlapping a [`Sensor`].
+///
+/// Each [`SensorExited`] message is paired with an earlier [`SensorEntered`] message for the same sensor and agent.
+/// No messages are written when the sensor itself is despawned.
+#[derive(Clone, Copy, Debug, Message, PartialEq, Eq)]
+pub struct SensorExited {
+    /// The sensor.
+    pub sensor: Entity,
+    /// The agent which stopped overlapping the sensor.
+    pub agent: Entity,
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub(crate) struct SensorState {
+    // The agents overlapping the sensor at the last update, sorted by entity.
+    overlapping: Vec<Entity>,
+}
+
+pub(crate) fn update(
+    query: AgentQuery,
+    mut sensors: Query<(Entity, &Agent, &AgentState, &ChildOf, &mut SensorState), With<Sensor>>,
+    agents: Query<&Agent, Without<Sensor>>,
+    mut entered: Local<Parallel<Vec<SensorEntered>>>,
+    mut exited: Local<Parallel<Vec<SensorExited>>>,
+    mut entered_writer: MessageWriter<SensorEntered>,
+    mut exited_writer: MessageWriter<SensorExited>,
+) {
+    sensors
+        .par_iter_mut()
+        .for_each(|(id, sensor, sensor_state, parent, mut state)| {
+            let mut overlapping: Vec<Entity> = if sensor_state.tile.is_some() {
+                query
+                    .intersecting(parent.0, sensor_state.position, sensor.radius())
+                    .filter(|&target| {
+                        agents.get(target).is_ok_and(|agent| {
+                            sensor
+                                .collision_groups()
+                                .collides_with(agent.collision_groups())
+                        })
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            overlapping.sort_unstable();
+
+            if overlapping == state.overlapping {
+                return;
+            }
+
+            let (mut old, mut new) = (
+                state.overlapping.iter().peekable(),
+                overlapping.iter().peekable(),
+            );
+            loop {
+                match (old.peek(), new.peek()) {
+                    (Some(&&o), Some(&&n)) if o == n => {
+                        old.next();
+                        new.next();
+                    }
+                    (Some(&&o), Some(&&n)) if o < n => {
+                        exited.borrow_local_mut().push(SensorExited {
+                            sensor: id,
+                            agent: o,
+                        });
+                        old.next();
+                    }
+                    (Some(&&o), None) => {
+                        exited.borrow_local_mut().push(SensorExited {
+                            sensor: id,
+                            agent: o,
+                        });
+                        old.next();
+                    }
+                    (_, Some(&&n)) => {
+                        entered.borrow_local_mut().push(SensorEntered {
+                            sensor: id,
+                            agent: n,
+                        });
+                        new.next();
+                    }
+                    (None, None) => break,
+                }
+            }
+
+            state.overlapping = overlapping;
+        });
+
+    entered_writer.write_batch(entered.drain());
+    exited_writer.write_batch(exited.drain());
+}
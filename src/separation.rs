@@ -0,0 +1,336 @@
+use bevy::{
+    ecs::system::{StaticSystemParam, SystemParamItem},
+    math::CompassQuadrant,
+    prelude::*,
+    utils::Parallel,
+};
+
+use crate::{
+    Agent, Layer,
+    agent::AgentState,
+    cache::TileCache,
+    collision::normal_vector,
+    obstacle::{Obstacle, ObstacleIndex, ObstacleState},
+    sensor::Sensor,
+    tile::{Tile, TileIndex, TileMap, TileRegion, Wall},
+};
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub(crate) fn separate<T>(
+    index: Res<TileIndex>,
+    obstacle_index: Res<ObstacleIndex>,
+    mut agents: ParamSet<(
+        Query<(
+            Entity,
+            &Agent,
+            &AgentState,
+            &Transform,
+            &ChildOf,
+            Has<Sensor>,
+        )>,
+        Query<&mut Transform, With<Agent>>,
+    )>,
+    obstacles: Query<(&Obstacle, &ObstacleState)>,
+    layers: Query<&Layer>,
+    caches: Query<&TileCache>,
+    map: StaticSystemParam<T>,
+    mut corrections: Local<Parallel<Vec<(Entity, Vec2)>>>,
+) where
+    T: TileMap,
+    for<'w, 's> SystemParamItem<'w, 's, T>: TileMap,
+{
+    let targets = agents.p0();
+    targets
+        .par_iter()
+        .for_each(|(id, agent, state, transform, parent, is_sensor)| {
+            let Some(tile) = state.tile else {
+                return;
+            };
+
+            let Ok(layer) = layers.get(parent.0) else {
+                return;
+            };
+
+            let strength = layer.separation();
+            if strength == 0.0 {
+                return;
+            }
+
+            let position = transform.translation.xy();
+            let mut correction = Vec2::ZERO;
+
+            if !is_sensor {
+                let neighborhood = state.reach - 1;
+                let (min, max) = (
+                    tile.tile() - IVec2::splat(neighborhood),
+                    tile.tile() + IVec2::splat(neighborhood),
+                );
+
+                let candidates = index.query(parent.0, min, max, |target| {
+                    let (_, target_agent, target_state, target_transform, _, target_is_sensor) =
+                        targets.get(target).ok()?;
+                    Some((
+                        (target_agent, target_transform, target_is_sensor),
+                        target_state.tile?.tile(),
+                    ))
+                });
+                for (target, (target_agent, target_transform, target_is_sensor)) in candidates {
+                    if target == id
+                        || target_is_sensor
+                        || !agent
+                            .collision_groups()
+                            .collides_with(target_agent.collision_groups())
+                    {
+                        continue;
+                    }
+
+                    let delta = position - target_transform.translation.xy();
+                    let overlap = agent.radius() + target_agent.radius() - delta.length();
+                    if overlap > 0.0 {
+                        // Agents at exactly the same position are separated along an arbitrary but consistent axis.
+                        let direction = delta.try_normalize().unwrap_or(if id < target {
+                            Vec2::X
+                        } else {
+                            Vec2::NEG_X
+                        });
+
+                        // Both agents move, so each resolves half of the overlap.
+                        correction += direction * overlap * 0.5 * strength;
+                    }
+                }
+            }
+
+            if agent.collision_groups().collides_with_walls() {
+                correction += obstacle_penetration(
+                    &obstacle_index,
+                    &obstacles,
+                    parent.0,
+                    layer,
+                    agent.radius(),
+                    position,
+                ) * strength;
+                correction += wall_penetration(
+                    &*map,
+                    caches.get(parent.0).ok(),
+                    parent.0,
+                    layer,
+                    agent.radius(),
+                    position,
+                ) * strength;
+            }
+
+            if correction != Vec2::ZERO {
+                corrections.borrow_local_mut().push((id, correction));
+            }
+        });
+
+    let mut transforms = agents.p1();
+    for (id, correction) in corrections.drain() {
+        if let Ok(mut transform) = transforms.get_mut(id) {
+            transform.translation.x += correction.x;
+            transform.translation.y += correction.y;
+        }
+    }
+}
+
+// Returns the displacement which moves an agent out of all obstacles it overlaps.
+fn obstacle_penetration(
+    index: &ObstacleIndex,
+    obstacles: &Query<(&Obstacle, &ObstacleState)>,
+    layer_id: Entity,
+    layer: &Layer,
+    radius: f32,
+    position: Vec2,
+) -> Vec2 {
+    let min = Tile::floor(layer_id, position - radius, layer.scale()).tile();
+    let max = Tile::floor(layer_id, position + radius, layer.scale()).tile();
+
+    let mut correction = Vec2::ZERO;
+    for (_, obstacle, state) in index.query(obstacles, layer_id, min, max) {
+        let delta = position - state.position;
+        let overlap = radius + obstacle.radius() - delta.length();
+        if overlap > 0.0 {
+            correction += delta.try_normalize().unwrap_or(Vec2::X) * overlap;
+        }
+    }
+
+    correction
+}
+
+// Returns the displacement which moves an agent out of all walls it overlaps. An agent whose center is inside a solid
+// tile is moved towards the nearest open neighbor.
+fn wall_penetration(
+    map: &impl TileMap,
+    cache: Option<&TileCache>,
+    layer_id: Entity,
+    layer: &Layer,
+    radius: f32,
+    position: Vec2,
+) -> Vec2 {
+    let tile_size = layer.tile_size();
+    let tile = Tile::floor(layer_id, position, layer.scale());
+
+    let region = TileRegion::new(
+        map,
+        cache,
+        layer_id,
+        Tile::floor(layer_id, position - radius, layer.scale()).tile(),
+        Tile::floor(layer_id, position + radius, layer.scale()).tile(),
+    );
+
+    if region.is_solid(tile.tile()) {
+        return region
+            .boundaries(tile.tile())
+            .map(|(wall_position, normal)| {
+                let depth = wall_depth(position, wall_position as f32 * tile_size, normal) + radius;
+                (depth, normal)
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map_or(Vec2::ZERO, |(depth, normal)| normal_vector(normal) * depth);
+    }
+
+    let mut correction = Vec2::ZERO;
+    for wall in region.walls() {
+        match wall {
+            Wall::Edge(wall_tile, normal) => {
+                let (wall_position, wall_start, tangent) = match normal {
+                    CompassQuadrant::North => (wall_tile.y, wall_tile.x, position.x),
+                    CompassQuadrant::East => (wall_tile.x, wall_tile.y, position.y),
+                    CompassQuadrant::South => (wall_tile.y + 1, wall_tile.x, position.x),
+                    CompassQuadrant::West => (wall_tile.x + 1, wall_tile.y, position.y),
+                };
+
+                // Agents beyond the ends of the edge overlap a neighboring edge or corner instead.
+                let wall_start = wall_start as f32 * tile_size;
+                if !(wall_start..wall_start + tile_size).contains(&tangent) {
+                    continue;
+                }
+
+                // Agents behind the edge are pushed out by the wall facing them instead.
+                let distance = -wall_depth(position, wall_position as f32 * tile_size, normal);
+                if (0.0..radius).contains(&distance) {
+                    correction += normal_vector(normal) * (radius - distance);
+                }
+            }
+            Wall::Face(start, end, normal) => {
+                let (start, end) = (start * tile_size, end * tile_size);
+
+                // Agents beyond the ends of the face overlap a neighboring face or vertex instead, and those behind it
+                // are pushed out by another face.
+                let tangent = (position - start).dot(end - start) / start.distance_squared(end);
+                let distance = (position - start).dot(normal);
+                if !(0.0..1.0).contains(&tangent) || distance < 0.0 {
+                    continue;
+                }
+
+                if distance < radius {
+                    correction += normal * (radius - distance);
+                }
+            }
+            Wall::Corner(corner) => {
+                let delta = position - corner.as_vec2() * tile_size;
+                let overlap = radius - delta.length();
+                if overlap > 0.0 {
+                    correction += delta.normalize_or_zero() * overlap;
+                }
+            }
+            Wall::Vertex(vertex) => {
+                let delta = position - vertex * tile_size;
+                let overlap = radius - delta.length();
+                if overlap > 0.0 {
+                    correction += delta.normalize_or_zero() * overlap;
+                }
+            }
+        }
+    }
+
+    correction
+}
+
+// Returns how far the given position is behind a wall, against the direction of its normal.
+fn wall_depth(position: Vec2, wall_position: f32, normal: CompassQuadrant) -> f32 {
+    match normal {
+        CompassQuadrant::North => wall_position - position.y,
+        CompassQuadrant::East => wall_position - position.x,
+        CompassQuadrant::South => position.y - wall_position,
+        CompassQuadrant::West => position.x - wall_position,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use bevy::{ecs::system::SystemParam, prelude::*};
+
+    use super::*;
+
+    #[derive(SystemParam)]
+    struct Floor;
+
+    impl TileMap for Floor {
+        fn is_solid(&self, _: Entity, tile: IVec2) -> bool {
+            tile.y < 0
+        }
+    }
+
+    #[derive(SystemParam)]
+    struct Block;
+
+    impl TileMap for Block {
+        fn is_solid(&self, _: Entity, tile: IVec2) -> bool {
+            tile == IVec2::ZERO
+        }
+    }
+
+    #[test]
+    fn wall_penetration_none() {
+        let correction = wall_penetration(
+            &Floor,
+            None,
+            Entity::PLACEHOLDER,
+            &Layer::default(),
+            0.2,
+            Vec2::new(0.5, 0.5),
+        );
+        assert_relative_eq!(correction, Vec2::ZERO);
+    }
+
+    #[test]
+    fn wall_penetration_edge() {
+        let correction = wall_penetration(
+            &Floor,
+            None,
+            Entity::PLACEHOLDER,
+            &Layer::default(),
+            0.2,
+            Vec2::new(1.0, 0.15),
+        );
+        assert_relative_eq!(correction, Vec2::new(0.0, 0.05));
+    }
+
+    #[test]
+    fn wall_penetration_embedded() {
+        let correction = wall_penetration(
+            &Floor,
+            None,
+            Entity::PLACEHOLDER,
+            &Layer::default(),
+            0.2,
+            Vec2::new(0.5, -0.3),
+        );
+        assert_relative_eq!(correction, Vec2::new(0.0, 0.5));
+    }
+
+    #[test]
+    fn wall_penetration_corner() {
+        let correction = wall_penetration(
+            &Block,
+            None,
+            Entity::PLACEHOLDER,
+            &Layer::default(),
+            0.6,
+            Vec2::new(1.3, 1.4),
+        );
+        assert_relative_eq!(correction, Vec2::new(0.06, 0.08));
+    }
+}
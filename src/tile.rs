@@ -6,20 +6,198 @@ use bevy::{
 };
 use smallvec::SmallVec;
 
+use crate::{
+    Layer,
+    cache::{Boundaries, TileCache},
+};
+
 /// A system parameter used to check whether a tile be collidable by agents.
 pub trait TileMap: SystemParam + Send + Sync {
-    /// Returns `true` if the tile at the given layer and coordinates is solid.
+    /// Whether the map has any thin walls or blocked edges.
+    ///
+    /// [`TileMap::is_edge_solid`] and [`TileMap::is_edge_blocked`] are only called if this is `true`, so maps which
+    /// implement either of them must set it. It is `false` by default, so other maps don't pay for reading edges.
+    const HAS_EDGES: bool = false;
+
+    /// Returns `true` if the tile at the given layer and coordinates is solid, or partially solid.
     ///
     /// A tile's coordinates are its bottom-left corner.
     fn is_solid(&self, layer: Entity, tile: IVec2) -> bool;
+
+    /// Returns the shape of the solid part of a tile, for which [`TileMap::is_solid`] returned `true`.
+    ///
+    /// The default implementation returns [`TileShape::Full`], so solid tiles fill their whole square.
+    fn shape(&self, layer: Entity, tile: IVec2) -> TileShape {
+        let _ = (layer, tile);
+        TileShape::Full
+    }
+
+    /// Returns `true` if there is a thin wall, such as a fence, on the given side of a tile.
+    ///
+    /// Agents collide with thin walls between two open tiles as with the edge of a solid tile. To avoid describing the
+    /// same edge twice, this is only called with [`CompassQuadrant::South`] or [`CompassQuadrant::West`], so the wall
+    /// between a tile and the one above it is found from the south side of the upper tile. It is only called if
+    /// [`TileMap::HAS_EDGES`] is `true`. The default implementation returns `false`.
+    fn is_edge_solid(&self, layer: Entity, tile: IVec2, side: CompassQuadrant) -> bool {
+        let _ = (layer, tile, side);
+        false
+    }
+
+    /// Returns `true` if agents in a tile may not leave it across the given side, such as the bottom of a ledge which
+    /// agents can drop off but not climb back up.
+    ///
+    /// Unlike [`TileMap::is_edge_solid`], this is called with every side of a tile, and only blocks agents crossing
+    /// the edge in one direction. Agents entering the tile across the same edge pass through it freely. It is only
+    /// called if [`TileMap::HAS_EDGES`] is `true`. The default implementation returns `false`.
+    fn is_edge_blocked(&self, layer: Entity, tile: IVec2, side: CompassQuadrant) -> bool {
+        let _ = (layer, tile, side);
+        false
+    }
+
+    /// Returns the factor by which the speed of agents in the tile is multiplied, such as `0.5` for mud or `1.5` for a
+    /// road.
+    ///
+    /// An agent moves at its [`Velocity`](crate::Velocity) multiplied by the factor for the tile containing it at the
+    /// start of each simulation step, and its [`Velocity`](crate::Velocity) itself is left unscaled. The factor must be
+    /// positive; tiles which agents can't move through should be solid instead. The default implementation returns
+    /// `1.0`.
+    fn speed_multiplier(&self, layer: Entity, tile: IVec2) -> f32 {
+        let _ = (layer, tile);
+        1.0
+    }
+
+    /// Sets a bit in `solid` for each solid tile in the rectangle between `min` and `max`, inclusive.
+    ///
+    /// Tiles are numbered in row-major order from `min`, so the tile at `(x, y)` has the index
+    /// `i = (y - min.y) * width + (x - min.x)`, and is solid if bit `i % 64` of `solid[i / 64]` is set. The `solid`
+    /// slice is initially zeroed, and long enough to hold a bit for every tile in the rectangle.
+    ///
+    /// The default implementation calls [`TileMap::is_solid`] for each tile. Implementations which can look up many
+    /// tiles at once more efficiently than individually should override it.
+    fn is_solid_region(&self, layer: Entity, min: IVec2, max: IVec2, solid: &mut [u64]) {
+        set_solid_bits(min, max, solid, |tile| self.is_solid(layer, tile));
+    }
+
+    /// Sets a bit in `solid` for each tile in the rectangle between `min` and `max`, inclusive, with a thin wall on the
+    /// given side, which is [`CompassQuadrant::South`] or [`CompassQuadrant::West`].
+    ///
+    /// The bits are laid out as in [`TileMap::is_solid_region`]. The default implementation calls
+    /// [`TileMap::is_edge_solid`] for each tile. Implementations which can look up many edges at once more efficiently
+    /// than individually should override it.
+    fn is_edge_solid_region(
+        &self,
+        layer: Entity,
+        min: IVec2,
+        max: IVec2,
+        side: CompassQuadrant,
+        solid: &mut [u64],
+    ) {
+        set_solid_bits(min, max, solid, |tile| {
+            self.is_edge_solid(layer, tile, side)
+        });
+    }
+
+    /// Sets a bit in `blocked` for each tile in the rectangle between `min` and `max`, inclusive, which agents may not
+    /// leave across the given side, as in [`TileMap::is_edge_blocked`].
+    ///
+    /// The bits are laid out as in [`TileMap::is_solid_region`]. The default implementation calls
+    /// [`TileMap::is_edge_blocked`] for each tile. Implementations which can look up many edges at once more
+    /// efficiently than individually should override it.
+    fn is_edge_blocked_region(
+        &self,
+        layer: Entity,
+        min: IVec2,
+        max: IVec2,
+        side: CompassQuadrant,
+        blocked: &mut [u64],
+    ) {
+        set_solid_bits(min, max, blocked, |tile| {
+            self.is_edge_blocked(layer, tile, side)
+        });
+    }
+}
+
+/// The shape of the solid part of a tile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TileShape {
+    /// The tile has no solid part.
+    #[default]
+    Empty,
+    /// The whole tile is solid.
+    Full,
+    /// The northern half of the tile is solid.
+    HalfNorth,
+    /// The eastern half of the tile is solid.
+    HalfEast,
+    /// The southern half of the tile is solid.
+    HalfSouth,
+    /// The western half of the tile is solid.
+    HalfWest,
+    /// The triangle in the north-east of the tile is solid.
+    DiagonalNorthEast,
+    /// The triangle in the north-west of the tile is solid.
+    DiagonalNorthWest,
+    /// The triangle in the south-east of the tile is solid.
+    DiagonalSouthEast,
+    /// The triangle in the south-west of the tile is solid.
+    DiagonalSouthWest,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) struct Tile(Entity, IVec2);
 
+// The solidity of a rectangular region of tiles, and the tiles bordering it.
+#[derive(Debug)]
+pub(crate) struct TileRegion {
+    layer: Entity,
+    min: IVec2,
+    max: IVec2,
+    tiles: RegionTiles,
+}
+
+// The tiles of a region, in row-major order.
+#[derive(Debug)]
+enum RegionTiles {
+    // Read from a `TileMap`.
+    Solid {
+        // A bit for each tile which is set if it is fully solid.
+        solid: SmallVec<[u64; 1]>,
+        // The shape of each tile which is partially solid, or nothing if there are none.
+        partial: SmallVec<[TileShape; 16]>,
+        // Four bits for each tile, one for each of `EDGES`, which are set if agents may not leave the tile across
+        // that edge.
+        blocked: SmallVec<[u64; 4]>,
+    },
+    // The walls of each tile, read from a `TileCache`.
+    Cached(SmallVec<[Boundaries; 16]>),
+}
+
+// The edges of a tile, with the offset of the neighbor across each edge and the normal of a wall facing into the tile.
+pub(crate) const EDGES: [(IVec2, CompassQuadrant); 4] = [
+    (IVec2::NEG_Y, CompassQuadrant::North),
+    (IVec2::NEG_X, CompassQuadrant::East),
+    (IVec2::Y, CompassQuadrant::South),
+    (IVec2::X, CompassQuadrant::West),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Wall {
+    // The edge between an open tile and a solid neighbor or wall, facing into the open tile.
+    Edge(IVec2, CompassQuadrant),
+    // A convex corner of solid tiles, at the given tile coordinates.
+    Corner(IVec2),
+    // A face of a partially solid tile between two points, in tile coordinates, with the given outward normal.
+    Face(Vec2, Vec2, Vec2),
+    // A convex vertex of a partially solid tile, in tile coordinates.
+    Vertex(Vec2),
+}
+
+// The number of agents which can be indexed in a tile without allocating.
+pub(crate) const INDEX_INLINE_CAPACITY: usize = 7;
+
 #[derive(Resource, Default, Debug)]
 pub(crate) struct TileIndex {
-    index: HashMap<Tile, SmallVec<[Entity; 7]>>,
+    index: HashMap<Tile, SmallVec<[Entity; INDEX_INLINE_CAPACITY]>>,
 }
 
 #[derive(Clone, Debug, Message, PartialEq, Eq)]
@@ -27,6 +205,8 @@ pub(crate) struct TileChanged {
     pub(crate) agent: Entity,
     pub(crate) old: Option<Tile>,
     pub(crate) new: Option<Tile>,
+    // The number of tiles in each direction around the agent's tile which it is indexed in.
+    pub(crate) reach: i32,
 }
 
 pub(crate) fn update_index(
@@ -63,59 +243,482 @@ impl Tile {
         self.tile().y
     }
 
-    pub(crate) fn neighborhood(&self) -> [Tile; 9] {
-        let layer = self.layer();
-        let (x, y) = (self.x(), self.y());
+    pub(crate) fn rect(layer: Entity, min: IVec2, max: IVec2) -> impl Iterator<Item = Tile> {
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| Tile::new(layer, x, y)))
+    }
 
-        [
-            Tile::new(layer, x - 1, y - 1),
-            Tile::new(layer, x, y - 1),
-            Tile::new(layer, x + 1, y - 1),
-            Tile::new(layer, x - 1, y),
-            Tile::new(layer, x, y),
-            Tile::new(layer, x + 1, y),
-            Tile::new(layer, x - 1, y + 1),
-            Tile::new(layer, x, y + 1),
-            Tile::new(layer, x + 1, y + 1),
-        ]
+    pub(crate) fn neighborhood(&self, reach: i32) -> impl Iterator<Item = Tile> {
+        Tile::rect(
+            self.layer(),
+            self.tile() - IVec2::splat(reach),
+            self.tile() + IVec2::splat(reach),
+        )
     }
 
-    pub(crate) fn boundaries(
-        &self,
-        map: &impl TileMap,
-    ) -> impl Iterator<Item = (i32, CompassQuadrant)> {
-        let layer = self.layer();
-        let (x, y) = (self.x(), self.y());
-
-        let solid = map.is_solid(layer, self.tile());
-
-        [
-            (IVec2::new(x, y - 1), y, CompassQuadrant::North),
-            (IVec2::new(x - 1, y), x, CompassQuadrant::East),
-            (IVec2::new(x, y + 1), y + 1, CompassQuadrant::South),
-            (IVec2::new(x + 1, y), x + 1, CompassQuadrant::West),
-        ]
-        .into_iter()
-        .filter_map(move |(adjacent, position, direction)| {
-            let adjacent_solid = map.is_solid(layer, adjacent);
-            match (solid, adjacent_solid) {
-                (false, false) | (true, true) => None,
-                (false, true) => Some((position, direction)),
-                (true, false) => Some((position, direction.opposite())),
+    // Returns the number of tiles around its own which an agent must be indexed in, so that any agents it may
+    // collide with can find it from their own tile or neighborhood.
+    pub(crate) fn reach(radius: f32, scale: f32) -> i32 {
+        ((2.0 * radius * scale).ceil() as i32).max(1)
+    }
+
+    // Returns the tiles crossed by a ray up to `max_distance`, and the distance along the ray at which each is entered.
+    //
+    // The tiles are visited in order, each sharing an edge with the last, so a tile is only visited once and the tiles
+    // within any rectangle are visited consecutively.
+    pub(crate) fn traverse(
+        layer_id: Entity,
+        layer: &Layer,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+    ) -> impl Iterator<Item = (Tile, f32)> {
+        let tile_size = layer.tile_size();
+        let start = Tile::floor(layer_id, origin, layer.scale()).tile();
+        let step = IVec2::new(
+            if direction.x < 0.0 { -1 } else { 1 },
+            if direction.y < 0.0 { -1 } else { 1 },
+        );
+
+        // The distance along the ray to the next tile boundary on each axis, and between boundaries on each axis.
+        let boundary = |tile: i32, step: i32, origin: f32, direction: f32| {
+            if direction == 0.0 {
+                f32::INFINITY
+            } else {
+                let next = if step > 0 {
+                    tile.saturating_add(1)
+                } else {
+                    tile
+                };
+                (next as f32 * tile_size - origin) / direction
             }
+        };
+        let mut next = Vec2::new(
+            boundary(start.x, step.x, origin.x, direction.x),
+            boundary(start.y, step.y, origin.y, direction.y),
+        );
+        let delta =
+            (tile_size / direction.abs()).map(|d| if d.is_nan() { f32::INFINITY } else { d });
+
+        let mut tile = start;
+        let mut entered = 0.0;
+        std::iter::from_fn(move || {
+            let current = (Tile::new(layer_id, tile.x, tile.y), entered);
+            if next.x < next.y {
+                tile.x = tile.x.saturating_add(step.x);
+                entered = next.x;
+                next.x += delta.x;
+            } else {
+                tile.y = tile.y.saturating_add(step.y);
+                entered = next.y;
+                next.y += delta.y;
+            }
+            Some(current)
+        })
+        .take_while(move |&(_, entered)| entered <= max_distance)
+        // A ray crosses at most one tile boundary per tile length on each axis, so this only stops the traversal early
+        // if the origin or direction are not finite.
+        .take((2.0 * max_distance / tile_size).ceil() as usize + 3)
+    }
+
+    // Returns the tiles crossed by the segment from `start` to `end`, with the distance along the segment at which each
+    // is entered and the part of the segment inside it.
+    //
+    // A segment whose length is not finite only visits the tile containing `start`.
+    pub(crate) fn traverse_segment(
+        layer_id: Entity,
+        layer: &Layer,
+        start: Vec2,
+        end: Vec2,
+    ) -> impl Iterator<Item = (Tile, f32, Vec2, Vec2)> {
+        let length = start.distance(end);
+        let (direction, length) = match (end - start).try_normalize() {
+            Some(direction) if length.is_finite() => (direction, length),
+            _ => (Vec2::X, 0.0),
+        };
+
+        let mut tiles = Tile::traverse(layer_id, layer, start, direction, length).peekable();
+        std::iter::from_fn(move || {
+            let (tile, entered) = tiles.next()?;
+            let exited = tiles.peek().map_or(length, |&(_, exited)| exited);
+            Some((
+                tile,
+                entered,
+                start + direction * entered,
+                start + direction * exited,
+            ))
         })
     }
 }
 
+impl TileRegion {
+    // Reads a region from the cache if it contains every tile, or from the tile map otherwise.
+    pub(crate) fn new<M: TileMap>(
+        map: &M,
+        cache: Option<&TileCache>,
+        layer: Entity,
+        min: IVec2,
+        max: IVec2,
+    ) -> Self {
+        let tiles = match cache.and_then(|cache| cache.get_rect(min - IVec2::ONE, max + IVec2::ONE))
+        {
+            Some(boundaries) => RegionTiles::Cached(boundaries),
+            None => {
+                let size = (max - min + 3).as_uvec2();
+                let mut solid = SmallVec::from_elem(0, (size.x * size.y).div_ceil(64) as usize);
+                map.is_solid_region(layer, min - IVec2::ONE, max + IVec2::ONE, &mut solid);
+
+                // Only fully solid tiles are kept in the bitmask.
+                let mut partial = SmallVec::new();
+                for (word_index, word) in solid.iter_mut().enumerate() {
+                    let mut bits = *word;
+                    while bits != 0 {
+                        let bit = bits.trailing_zeros();
+                        bits &= bits - 1;
+
+                        let i = word_index as u32 * 64 + bit;
+                        let tile = min - IVec2::ONE + UVec2::new(i % size.x, i / size.x).as_ivec2();
+                        let shape = map.shape(layer, tile);
+                        if shape != TileShape::Full {
+                            *word &= !(1 << bit);
+                            if shape != TileShape::Empty {
+                                if partial.is_empty() {
+                                    partial.resize((size.x * size.y) as usize, TileShape::Empty);
+                                }
+                                partial[i as usize] = shape;
+                            }
+                        }
+                    }
+                }
+
+                // Maps without edges leave the blocked bits empty, so no edge is blocked.
+                let blocked = if M::HAS_EDGES {
+                    blocked_bits(
+                        min,
+                        max,
+                        |min, max, side, solid| {
+                            map.is_edge_solid_region(layer, min, max, side, solid)
+                        },
+                        |min, max, side, blocked| {
+                            map.is_edge_blocked_region(layer, min, max, side, blocked)
+                        },
+                    )
+                } else {
+                    SmallVec::new()
+                };
+
+                RegionTiles::Solid {
+                    solid,
+                    partial,
+                    blocked,
+                }
+            }
+        };
+
+        TileRegion {
+            layer,
+            min,
+            max,
+            tiles,
+        }
+    }
+
+    #[cfg(test)]
+    fn from_fn(
+        layer: Entity,
+        min: IVec2,
+        max: IVec2,
+        shape: impl Fn(IVec2) -> TileShape,
+        is_edge_solid: impl Fn(IVec2, CompassQuadrant) -> bool,
+        is_edge_blocked: impl Fn(IVec2, CompassQuadrant) -> bool,
+    ) -> Self {
+        let size = (max - min + 3).as_uvec2();
+        let mut solid = SmallVec::from_elem(0, (size.x * size.y).div_ceil(64) as usize);
+        set_solid_bits(min - IVec2::ONE, max + IVec2::ONE, &mut solid, |tile| {
+            shape(tile) == TileShape::Full
+        });
+        let partial = Tile::rect(layer, min - IVec2::ONE, max + IVec2::ONE)
+            .map(|tile| match shape(tile.tile()) {
+                shape if shape.is_partial() => shape,
+                _ => TileShape::Empty,
+            })
+            .collect();
+        let blocked = blocked_bits(
+            min,
+            max,
+            |min, max, side, solid| {
+                set_solid_bits(min, max, solid, |tile| is_edge_solid(tile, side));
+            },
+            |min, max, side, blocked| {
+                set_solid_bits(min, max, blocked, |tile| is_edge_blocked(tile, side));
+            },
+        );
+        TileRegion {
+            layer,
+            min,
+            max,
+            tiles: RegionTiles::Solid {
+                solid,
+                partial,
+                blocked,
+            },
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_cached(&self) -> bool {
+        matches!(self.tiles, RegionTiles::Cached(_))
+    }
+
+    // Returns the index of a tile within the region or its border.
+    fn index(&self, tile: IVec2) -> usize {
+        let offset = tile - self.min + IVec2::ONE;
+        let width = self.max.x - self.min.x + 3;
+        (offset.y * width + offset.x) as usize
+    }
+
+    // Returns `true` if the tile is fully solid.
+    pub(crate) fn is_solid(&self, tile: IVec2) -> bool {
+        let i = self.index(tile);
+        match &self.tiles {
+            RegionTiles::Solid { solid, .. } => solid[i / 64] & (1 << (i % 64)) != 0,
+            RegionTiles::Cached(boundaries) => boundaries[i].shape() == TileShape::Full,
+        }
+    }
+
+    pub(crate) fn shape(&self, tile: IVec2) -> TileShape {
+        match &self.tiles {
+            RegionTiles::Solid { .. } if self.is_solid(tile) => TileShape::Full,
+            RegionTiles::Solid { partial, .. } => partial
+                .get(self.index(tile))
+                .copied()
+                .unwrap_or(TileShape::Empty),
+            RegionTiles::Cached(boundaries) => boundaries[self.index(tile)].shape(),
+        }
+    }
+
+    // Returns `true` if agents may not leave a tile, which must be within the region or its border, across the given
+    // edge, as an index into `EDGES`.
+    fn is_blocked(&self, tile: IVec2, edge: usize) -> bool {
+        match &self.tiles {
+            RegionTiles::Solid { blocked, .. } => {
+                let i = self.index(tile) * EDGES.len() + edge;
+                blocked
+                    .get(i / 64)
+                    .is_some_and(|word| word & (1 << (i % 64)) != 0)
+            }
+            RegionTiles::Cached(_) => unreachable!("blocked edges are cached as edges"),
+        }
+    }
+
+    // Returns `true` if the given edge of a tile is blocked in both directions.
+    fn is_thin_wall(&self, tile: IVec2, edge: usize) -> bool {
+        let (offset, _) = EDGES[edge];
+        self.is_blocked(tile, edge) && self.is_blocked(tile + offset, (edge + 2) % EDGES.len())
+    }
+
+    // Returns `true` if there is a wall on the given edge of a tile, as an index into `EDGES`. For a solid tile, this
+    // is when it borders an open tile, and for an open tile, when it borders a solid tile or agents may not leave it
+    // across the edge.
+    pub(crate) fn is_edge(&self, tile: IVec2, edge: usize) -> bool {
+        if let RegionTiles::Cached(boundaries) = &self.tiles {
+            return boundaries[self.index(tile)].is_edge(edge);
+        }
+
+        let (offset, _) = EDGES[edge];
+        let solid = self.is_solid(tile);
+        if solid != self.is_solid(tile + offset) {
+            return true;
+        }
+
+        !solid && self.is_blocked(tile, edge)
+    }
+
+    // Returns `true` if the given point is a convex corner of solid tiles or thin walls, which are only checked within
+    // the region and its border.
+    pub(crate) fn is_convex_corner(&self, corner: IVec2) -> bool {
+        if let RegionTiles::Cached(boundaries) = &self.tiles {
+            return boundaries[self.index(corner)].is_corner();
+        }
+
+        let south_west = self.is_solid(corner - IVec2::ONE);
+        let south_east = self.is_solid(corner - IVec2::Y);
+        let north_west = self.is_solid(corner - IVec2::X);
+        let north_east = self.is_solid(corner);
+        // A corner is convex if it has a single solid tile, or two diagonally opposite solid tiles.
+        if matches!(
+            (south_west, south_east, north_west, north_east),
+            (true, false, false, false)
+                | (false, true, false, false)
+                | (false, false, true, false)
+                | (false, false, false, true)
+                | (true, false, false, true)
+                | (false, true, true, false)
+        ) {
+            return true;
+        }
+
+        // The thin walls on each side of the corner. Edges which are only blocked in one direction have no corners, as
+        // agents may pass them from the other side.
+        let north_thin = self.is_thin_wall(corner, 1);
+        let east_thin = self.is_thin_wall(corner, 0);
+        let south_thin = self.is_thin_wall(corner - IVec2::Y, 1);
+        let west_thin = self.is_thin_wall(corner - IVec2::X, 0);
+        if !(north_thin || east_thin || south_thin || west_thin) {
+            return false;
+        }
+
+        // Otherwise, the end of a thin wall is convex if it can be reached from an open tile without crossing a wall.
+        let north = north_thin || north_west != north_east;
+        let east = east_thin || south_east != north_east;
+        let south = south_thin || south_west != south_east;
+        let west = west_thin || south_west != north_west;
+        (!north_east && !north && !east)
+            || (!north_west && !north && !west)
+            || (!south_east && !south && !east)
+            || (!south_west && !south && !west)
+    }
+
+    // Returns the edges of the given tile, which must be within the region, that border a tile of the opposite
+    // solidity. For a solid tile, the normals face out of it.
+    pub(crate) fn boundaries(&self, tile: IVec2) -> impl Iterator<Item = (i32, CompassQuadrant)> {
+        let solid = self.is_solid(tile);
+
+        EDGES
+            .into_iter()
+            .enumerate()
+            .filter(move |&(edge, _)| self.is_edge(tile, edge))
+            .map(move |(_, (_, direction))| {
+                let position = match direction {
+                    CompassQuadrant::North => tile.y,
+                    CompassQuadrant::East => tile.x,
+                    CompassQuadrant::South => tile.y + 1,
+                    CompassQuadrant::West => tile.x + 1,
+                };
+                if solid {
+                    (position, direction.opposite())
+                } else {
+                    (position, direction)
+                }
+            })
+    }
+
+    // Returns `true` if the region contains every tile in the rectangle between `min` and `max`, inclusive.
+    pub(crate) fn contains(&self, min: IVec2, max: IVec2) -> bool {
+        self.min.cmple(min).all() && max.cmple(self.max).all()
+    }
+
+    // Returns all walls an agent within the region may collide with.
+    pub(crate) fn walls(&self) -> impl Iterator<Item = Wall> {
+        self.walls_in(self.min, self.max)
+    }
+
+    // Returns the walls an agent within the rectangle between `min` and `max`, which must be inside the region, may
+    // collide with.
+    pub(crate) fn walls_in(&self, min: IVec2, max: IVec2) -> impl Iterator<Item = Wall> {
+        debug_assert!(self.contains(min, max));
+        let edges = Tile::rect(self.layer, min, max)
+            .map(|tile| tile.tile())
+            .filter(|&tile| !self.is_solid(tile))
+            .flat_map(move |tile| {
+                EDGES
+                    .into_iter()
+                    .enumerate()
+                    .filter(move |&(edge, _)| self.is_edge(tile, edge))
+                    // Edges hidden behind the solid part of a partially solid tile can't be reached.
+                    .filter(move |&(_, (_, normal))| {
+                        !self.shape(tile).covers(normal.opposite(), 0.0, 1.0)
+                    })
+                    .map(move |(_, (_, normal))| Wall::Edge(tile, normal))
+            });
+
+        let corners = Tile::rect(self.layer, min, max + IVec2::ONE)
+            .map(|tile| tile.tile())
+            .filter(|&corner| self.is_convex_corner(corner) && !self.is_partial_vertex(corner))
+            .map(Wall::Corner);
+
+        let partial = Tile::rect(self.layer, min, max)
+            .map(|tile| tile.tile())
+            .filter(|&tile| self.shape(tile).is_partial())
+            .flat_map(|tile| self.partial_walls(tile));
+
+        edges.chain(corners).chain(partial)
+    }
+
+    // Returns `true` if the given point is a vertex of a partially solid tile, in which case any walls there are
+    // found from the faces of that tile instead.
+    fn is_partial_vertex(&self, corner: IVec2) -> bool {
+        [IVec2::ONE, IVec2::Y, IVec2::X, IVec2::ZERO]
+            .into_iter()
+            .any(|offset| {
+                let shape = self.shape(corner - offset);
+                shape.is_partial() && shape.vertices().contains(&offset.as_vec2())
+            })
+    }
+
+    // Returns the faces and vertices of a partially solid tile which aren't covered by its neighbors.
+    fn partial_walls(&self, tile: IVec2) -> impl Iterator<Item = Wall> {
+        let vertices = self.shape(tile).vertices();
+        let origin = tile.as_vec2();
+
+        // Whether each face, from the vertex with the same index to the next, is exposed.
+        let mut exposed = [false; 4];
+        for (i, (&start, &end)) in vertices
+            .iter()
+            .zip(vertices.iter().cycle().skip(1))
+            .enumerate()
+        {
+            exposed[i] = match TileShape::side(start, end) {
+                Some((offset, side)) => {
+                    let (from, to) = match side {
+                        CompassQuadrant::North | CompassQuadrant::South => (start.x, end.x),
+                        CompassQuadrant::East | CompassQuadrant::West => (start.y, end.y),
+                    };
+                    !self
+                        .shape(tile + offset)
+                        .covers(side, from.min(to), from.max(to))
+                }
+                None => true,
+            };
+        }
+
+        let count = vertices.len();
+        let faces = (0..count).filter(move |&i| exposed[i]).map(move |i| {
+            let (start, end) = (vertices[i], vertices[(i + 1) % count]);
+            // The vertices are counter-clockwise, so the outward normal is to the right of each face.
+            let normal = -(end - start).perp().normalize();
+            Wall::Face(origin + start, origin + end, normal)
+        });
+        // A vertex is only convex and exposed if both faces meeting at it are.
+        let vertices = (0..count)
+            .filter(move |&i| exposed[i] && exposed[(i + count - 1) % count])
+            .map(move |i| Wall::Vertex(origin + vertices[i]));
+
+        faces.chain(vertices)
+    }
+}
+
 impl TileIndex {
     fn update(&mut self, event: &TileChanged) {
+        let reach = event.reach;
         match (event.old, event.new) {
             (None, None) => {}
-            (Some(old), None) => self.remove_neighborhood(event.agent, old),
-            (None, Some(new)) => self.insert_neighborhood(event.agent, new),
+            (Some(old), None) => self.remove_neighborhood(event.agent, old, reach),
+            (None, Some(new)) => self.insert_neighborhood(event.agent, new, reach),
             (Some(old), Some(new)) if old.layer() != new.layer() => {
-                self.remove_neighborhood(event.agent, old);
-                self.insert_neighborhood(event.agent, new);
+                self.remove_neighborhood(event.agent, old, reach);
+                self.insert_neighborhood(event.agent, new, reach);
+            }
+            (Some(old), Some(new)) if reach != 1 => {
+                for tile in old.neighborhood(reach) {
+                    if tile.tile().chebyshev_distance(new.tile()) > reach as u32 {
+                        self.remove(event.agent, tile);
+                    }
+                }
+                for tile in new.neighborhood(reach) {
+                    if tile.tile().chebyshev_distance(old.tile()) > reach as u32 {
+                        self.insert(event.agent, tile);
+                    }
+                }
             }
             (Some(old), Some(new)) => {
                 let layer = old.layer();
@@ -145,26 +748,48 @@ impl TileIndex {
                         self.insert(event.agent, Tile::new(layer, nx + dx, ny - dy));
                     }
                     _ => {
-                        self.remove_neighborhood(event.agent, old);
-                        self.insert_neighborhood(event.agent, new);
+                        self.remove_neighborhood(event.agent, old, reach);
+                        self.insert_neighborhood(event.agent, new, reach);
                     }
                 }
             }
         }
     }
 
-    fn insert_neighborhood(&mut self, agent: Entity, tile: Tile) {
-        for t in tile.neighborhood() {
+    fn insert_neighborhood(&mut self, agent: Entity, tile: Tile, reach: i32) {
+        for t in tile.neighborhood(reach) {
             self.insert(agent, t);
         }
     }
 
-    fn remove_neighborhood(&mut self, agent: Entity, tile: Tile) {
-        for t in tile.neighborhood() {
+    fn remove_neighborhood(&mut self, agent: Entity, tile: Tile, reach: i32) {
+        for t in tile.neighborhood(reach) {
             self.remove(agent, t);
         }
     }
 
+    // Indexes an agent in the neighborhood of every tile along a path, such as the tiles it passes through in a step.
+    pub(crate) fn insert_path(
+        &mut self,
+        id: Entity,
+        path: impl IntoIterator<Item = Tile>,
+        reach: i32,
+    ) {
+        for tile in path {
+            for neighbor in tile.neighborhood(reach) {
+                let agents = self.index.entry(neighbor).or_default();
+                // Neighboring tiles of the path share most of their neighborhood, so skip tiles already indexed.
+                if agents.last() != Some(&id) {
+                    agents.push(id);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.index.clear();
+    }
+
     fn insert(&mut self, id: Entity, tile: Tile) {
         self.index.entry(tile).or_default().push(id);
     }
@@ -190,6 +815,206 @@ impl TileIndex {
             None => &[],
         }
     }
+
+    // Returns each agent indexed in the tiles of a layer from `min` to `max`, with the data returned by `lookup`, which
+    // also gives the tile the agent is in. Agents for which `lookup` returns `None` are skipped.
+    //
+    // Agents are indexed in every tile within their reach, so may be found in several of the queried tiles. Each agent
+    // is only returned from the queried tile nearest its own, so it is returned at most once.
+    pub(crate) fn query<'a, D>(
+        &'a self,
+        layer: Entity,
+        min: IVec2,
+        max: IVec2,
+        lookup: impl Fn(Entity) -> Option<(D, IVec2)> + Copy + 'a,
+    ) -> impl Iterator<Item = (Entity, D)> + 'a
+    where
+        D: 'a,
+    {
+        Tile::rect(layer, min, max).flat_map(move |query_tile| {
+            self.get(query_tile).iter().filter_map(move |&id| {
+                let (data, tile) = lookup(id)?;
+                (tile.clamp(min, max) == query_tile.tile()).then_some((id, data))
+            })
+        })
+    }
+
+    // Returns the number of tiles with at least one agent indexed in them.
+    #[cfg(feature = "diagnostic")]
+    pub(crate) fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    // Returns each tile with at least one agent indexed in it, and those agents.
+    #[cfg(any(feature = "debug", feature = "diagnostic"))]
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (Tile, &[Entity])> {
+        self.index
+            .iter()
+            .map(|(&tile, agents)| (tile, agents.as_slice()))
+    }
+}
+
+impl TileShape {
+    // Returns `true` if the tile is solid, but not fully.
+    pub(crate) fn is_partial(self) -> bool {
+        !matches!(self, TileShape::Empty | TileShape::Full)
+    }
+
+    // Returns the vertices of the solid part of the tile, relative to its bottom-left corner, in counter-clockwise
+    // order.
+    pub(crate) fn vertices(self) -> &'static [Vec2] {
+        match self {
+            TileShape::Empty => &[],
+            TileShape::Full => &[Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y],
+            TileShape::HalfNorth => {
+                const { &[Vec2::new(0.0, 0.5), Vec2::new(1.0, 0.5), Vec2::ONE, Vec2::Y] }
+            }
+            TileShape::HalfEast => {
+                const { &[Vec2::new(0.5, 0.0), Vec2::X, Vec2::ONE, Vec2::new(0.5, 1.0)] }
+            }
+            TileShape::HalfSouth => {
+                const {
+                    &[
+                        Vec2::ZERO,
+                        Vec2::X,
+                        Vec2::new(1.0, 0.5),
+                        Vec2::new(0.0, 0.5),
+                    ]
+                }
+            }
+            TileShape::HalfWest => {
+                const {
+                    &[
+                        Vec2::ZERO,
+                        Vec2::new(0.5, 0.0),
+                        Vec2::new(0.5, 1.0),
+                        Vec2::Y,
+                    ]
+                }
+            }
+            TileShape::DiagonalNorthEast => &[Vec2::X, Vec2::ONE, Vec2::Y],
+            TileShape::DiagonalNorthWest => &[Vec2::ZERO, Vec2::ONE, Vec2::Y],
+            TileShape::DiagonalSouthEast => &[Vec2::ZERO, Vec2::X, Vec2::ONE],
+            TileShape::DiagonalSouthWest => &[Vec2::ZERO, Vec2::X, Vec2::Y],
+        }
+    }
+
+    // Returns the offset of the neighbor across the face between two vertices, and the side of the neighbor which the
+    // face lies on, if the face is on the edge of the tile.
+    fn side(start: Vec2, end: Vec2) -> Option<(IVec2, CompassQuadrant)> {
+        if start.y == 0.0 && end.y == 0.0 {
+            Some((IVec2::NEG_Y, CompassQuadrant::North))
+        } else if start.x == 0.0 && end.x == 0.0 {
+            Some((IVec2::NEG_X, CompassQuadrant::East))
+        } else if start.y == 1.0 && end.y == 1.0 {
+            Some((IVec2::Y, CompassQuadrant::South))
+        } else if start.x == 1.0 && end.x == 1.0 {
+            Some((IVec2::X, CompassQuadrant::West))
+        } else {
+            None
+        }
+    }
+
+    // Returns `true` if the solid part of the tile covers the given range of one of its sides.
+    fn covers(self, side: CompassQuadrant, from: f32, to: f32) -> bool {
+        let vertices = self.vertices();
+        vertices
+            .iter()
+            .zip(vertices.iter().cycle().skip(1))
+            .any(|(&start, &end)| {
+                let (on_side, start, end) = match side {
+                    CompassQuadrant::North => (start.y == 1.0 && end.y == 1.0, start.x, end.x),
+                    CompassQuadrant::East => (start.x == 1.0 && end.x == 1.0, start.y, end.y),
+                    CompassQuadrant::South => (start.y == 0.0 && end.y == 0.0, start.x, end.x),
+                    CompassQuadrant::West => (start.x == 0.0 && end.x == 0.0, start.y, end.y),
+                };
+                on_side && start.min(end) <= from && start.max(end) >= to
+            })
+    }
+
+    // Returns `true` if a circle, with its center relative to the tile's bottom-left corner and in units of tiles,
+    // overlaps the solid part of the tile.
+    pub(crate) fn overlaps_circle(self, center: Vec2, radius: f32) -> bool {
+        let vertices = self.vertices();
+        if vertices.is_empty() {
+            return false;
+        }
+
+        let mut inside = true;
+        let mut distance = f32::INFINITY;
+        for (&start, &end) in vertices.iter().zip(vertices.iter().cycle().skip(1)) {
+            let edge = end - start;
+            let t = ((center - start).dot(edge) / edge.length_squared()).clamp(0.0, 1.0);
+            distance = distance.min(center.distance(start + edge * t));
+            // The polygon is counter-clockwise, so points inside are to the left of every face.
+            inside &= edge.perp_dot(center - start) >= 0.0;
+        }
+
+        inside || distance < radius
+    }
+}
+
+// Sets the bit for each solid tile in a rectangle, in the layout used by `TileMap::is_solid_region`.
+fn set_solid_bits(min: IVec2, max: IVec2, solid: &mut [u64], is_solid: impl Fn(IVec2) -> bool) {
+    for (i, tile) in Tile::rect(Entity::PLACEHOLDER, min, max).enumerate() {
+        if is_solid(tile.tile()) {
+            solid[i / 64] |= 1 << (i % 64);
+        }
+    }
+}
+
+// Returns a bit for each edge of each tile in the region between `min` and `max` and its border, which is set if
+// agents may not leave the tile across it, either because of a thin wall or because the edge is blocked in that
+// direction.
+fn blocked_bits(
+    min: IVec2,
+    max: IVec2,
+    is_edge_solid_region: impl Fn(IVec2, IVec2, CompassQuadrant, &mut [u64]),
+    is_edge_blocked_region: impl Fn(IVec2, IVec2, CompassQuadrant, &mut [u64]),
+) -> SmallVec<[u64; 4]> {
+    let (min, max) = (min - IVec2::ONE, max + IVec2::ONE);
+    let size = (max - min + 1).as_uvec2();
+
+    // Thin walls are only described from the south and west sides of tiles, so the walls on the north and east sides
+    // of the region are read from the row above and the column to the right of it.
+    let wall_size = size + 1;
+    let walls = [CompassQuadrant::South, CompassQuadrant::West].map(|side| {
+        let mut walls: SmallVec<[u64; 2]> =
+            SmallVec::from_elem(0, (wall_size.x * wall_size.y).div_ceil(64) as usize);
+        is_edge_solid_region(min, max + IVec2::ONE, side, &mut walls);
+        walls
+    });
+    let is_wall = |side: usize, offset: IVec2| {
+        let i = (offset.y as u32 * wall_size.x + offset.x as u32) as usize;
+        walls[side][i / 64] & (1 << (i % 64)) != 0
+    };
+
+    // The tiles which agents may not leave across each side, in the order of `EDGES`.
+    let blocked_sides = EDGES.map(|(_, normal)| {
+        let mut blocked: SmallVec<[u64; 1]> =
+            SmallVec::from_elem(0, (size.x * size.y).div_ceil(64) as usize);
+        is_edge_blocked_region(min, max, normal.opposite(), &mut blocked);
+        blocked
+    });
+
+    let mut blocked = SmallVec::from_elem(0, (size.x * size.y * 4).div_ceil(64) as usize);
+    for i in 0..(size.x * size.y) as usize {
+        let offset = UVec2::new(i as u32 % size.x, i as u32 / size.x).as_ivec2();
+        // The thin walls on each side of the tile, in the order of `EDGES`.
+        let thin_walls = [
+            is_wall(0, offset),
+            is_wall(1, offset),
+            is_wall(0, offset + IVec2::Y),
+            is_wall(1, offset + IVec2::X),
+        ];
+        for edge in 0..EDGES.len() {
+            if thin_walls[edge] || blocked_sides[edge][i / 64] & (1 << (i % 64)) != 0 {
+                let i = i * EDGES.len() + edge;
+                blocked[i / 64] |= 1 << (i % 64);
+            }
+        }
+    }
+    blocked
 }
 
 /// A default implementation of [`TileMap`] that treats all tiles as non-solid.
@@ -202,6 +1027,7 @@ impl TileMap for () {
 
 #[cfg(test)]
 mod tests {
+    use approx::assert_relative_eq;
     use bevy::prelude::*;
 
     use super::*;
@@ -242,6 +1068,329 @@ mod tests {
         assert_eq!(tile.tile(), IVec2::new(5, -3));
     }
 
+    #[test]
+    fn rect() {
+        let tiles: Vec<_> = Tile::rect(Entity::PLACEHOLDER, IVec2::new(-1, 2), IVec2::new(0, 3))
+            .map(|tile| tile.tile())
+            .collect();
+        assert_eq!(
+            tiles,
+            vec![
+                IVec2::new(-1, 2),
+                IVec2::new(0, 2),
+                IVec2::new(-1, 3),
+                IVec2::new(0, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn rect_empty() {
+        let mut tiles = Tile::rect(Entity::PLACEHOLDER, IVec2::new(1, 0), IVec2::new(0, 0));
+        assert!(tiles.next().is_none());
+    }
+
+    #[test]
+    fn region_walls_open() {
+        let region = region(&[], IVec2::ZERO, IVec2::ONE);
+        assert_eq!(region.walls().collect::<Vec<_>>(), vec![]);
+    }
+
+    #[test]
+    fn region_walls_edge() {
+        let region = region(&[IVec2::new(1, 0)], IVec2::ZERO, IVec2::ZERO);
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![
+                Wall::Edge(IVec2::ZERO, CompassQuadrant::West),
+                Wall::Corner(IVec2::new(1, 0)),
+                Wall::Corner(IVec2::new(1, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_walls_diagonal_corner() {
+        let region = region(&[IVec2::new(1, 1)], IVec2::ZERO, IVec2::ZERO);
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![Wall::Corner(IVec2::new(1, 1))]
+        );
+    }
+
+    #[test]
+    fn region_walls_straight_wall() {
+        let region = region(
+            &[IVec2::new(0, -1), IVec2::new(1, -1), IVec2::new(2, -1)],
+            IVec2::ZERO,
+            IVec2::new(1, 0),
+        );
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![
+                Wall::Edge(IVec2::new(0, 0), CompassQuadrant::North),
+                Wall::Edge(IVec2::new(1, 0), CompassQuadrant::North),
+                Wall::Corner(IVec2::new(0, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_walls_wall_end() {
+        let region = region(&[IVec2::new(0, -1)], IVec2::ZERO, IVec2::new(1, 0));
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![
+                Wall::Edge(IVec2::new(0, 0), CompassQuadrant::North),
+                Wall::Corner(IVec2::new(0, 0)),
+                Wall::Corner(IVec2::new(1, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_walls_concave_corner() {
+        let region = region(
+            &[IVec2::new(-1, 0), IVec2::new(-1, -1), IVec2::new(0, -1)],
+            IVec2::ZERO,
+            IVec2::ZERO,
+        );
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![
+                Wall::Edge(IVec2::ZERO, CompassQuadrant::North),
+                Wall::Edge(IVec2::ZERO, CompassQuadrant::East),
+                Wall::Corner(IVec2::new(1, 0)),
+                Wall::Corner(IVec2::new(0, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_walls_solid_tile() {
+        let region = region(&[IVec2::ZERO], IVec2::ZERO, IVec2::ZERO);
+        assert!(region.is_solid(IVec2::ZERO));
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![
+                Wall::Corner(IVec2::new(0, 0)),
+                Wall::Corner(IVec2::new(1, 0)),
+                Wall::Corner(IVec2::new(0, 1)),
+                Wall::Corner(IVec2::new(1, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_walls_in() {
+        let region = region(&[IVec2::new(3, 0)], IVec2::ZERO, IVec2::new(2, 0));
+        assert!(region.contains(IVec2::ZERO, IVec2::ZERO));
+        assert!(!region.contains(IVec2::ZERO, IVec2::new(3, 0)));
+        assert_eq!(
+            region
+                .walls_in(IVec2::ZERO, IVec2::ZERO)
+                .collect::<Vec<_>>(),
+            vec![]
+        );
+        assert_eq!(
+            region
+                .walls_in(IVec2::new(2, 0), IVec2::new(2, 0))
+                .collect::<Vec<_>>(),
+            vec![
+                Wall::Edge(IVec2::new(2, 0), CompassQuadrant::West),
+                Wall::Corner(IVec2::new(3, 0)),
+                Wall::Corner(IVec2::new(3, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_is_solid() {
+        let solid = [
+            IVec2::new(-1, -1),
+            IVec2::new(2, 0),
+            IVec2::new(8, 6),
+            IVec2::new(0, 7),
+        ];
+        let region = region(&solid, IVec2::new(0, 0), IVec2::new(7, 6));
+        for tile in Tile::rect(Entity::PLACEHOLDER, IVec2::new(-1, -1), IVec2::new(8, 7)) {
+            assert_eq!(
+                region.is_solid(tile.tile()),
+                solid.contains(&tile.tile()),
+                "{tile:?}"
+            );
+        }
+        assert!(matches!(region.tiles, RegionTiles::Solid { solid, .. } if solid.len() == 2));
+    }
+
+    #[test]
+    fn region_walls_half_tile() {
+        let region = region_with_shapes(
+            &[(IVec2::ZERO, TileShape::HalfSouth)],
+            IVec2::ZERO,
+            IVec2::ZERO,
+        );
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![
+                Wall::Face(Vec2::ZERO, Vec2::X, Vec2::NEG_Y),
+                Wall::Face(Vec2::X, Vec2::new(1.0, 0.5), Vec2::X),
+                Wall::Face(Vec2::new(1.0, 0.5), Vec2::new(0.0, 0.5), Vec2::Y),
+                Wall::Face(Vec2::new(0.0, 0.5), Vec2::ZERO, Vec2::NEG_X),
+                Wall::Vertex(Vec2::ZERO),
+                Wall::Vertex(Vec2::X),
+                Wall::Vertex(Vec2::new(1.0, 0.5)),
+                Wall::Vertex(Vec2::new(0.0, 0.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_walls_half_tile_floor() {
+        let region = region_with_shapes(
+            &[
+                (IVec2::new(-1, -1), TileShape::Full),
+                (IVec2::new(0, -1), TileShape::Full),
+                (IVec2::new(1, -1), TileShape::Full),
+                (IVec2::new(2, -1), TileShape::Full),
+                (IVec2::new(0, 0), TileShape::HalfSouth),
+                (IVec2::new(1, 0), TileShape::HalfSouth),
+            ],
+            IVec2::ZERO,
+            IVec2::new(1, 0),
+        );
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![
+                Wall::Face(Vec2::new(1.0, 0.5), Vec2::new(0.0, 0.5), Vec2::Y),
+                Wall::Face(Vec2::new(0.0, 0.5), Vec2::ZERO, Vec2::NEG_X),
+                Wall::Vertex(Vec2::new(0.0, 0.5)),
+                Wall::Face(Vec2::new(2.0, 0.0), Vec2::new(2.0, 0.5), Vec2::X),
+                Wall::Face(Vec2::new(2.0, 0.5), Vec2::new(1.0, 0.5), Vec2::Y),
+                Wall::Vertex(Vec2::new(2.0, 0.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_walls_diagonal_tile() {
+        let region = region_with_shapes(
+            &[
+                (IVec2::new(-1, 0), TileShape::Full),
+                (IVec2::new(0, -1), TileShape::Full),
+                (IVec2::new(0, 0), TileShape::DiagonalSouthWest),
+            ],
+            IVec2::ZERO,
+            IVec2::ZERO,
+        );
+        let faces: Vec<_> = region
+            .walls()
+            .filter(|wall| matches!(wall, Wall::Face(..) | Wall::Vertex(_)))
+            .collect();
+        assert_eq!(
+            faces,
+            vec![Wall::Face(Vec2::X, Vec2::Y, Vec2::ONE.normalize())]
+        );
+    }
+
+    #[test]
+    fn region_walls_thin_wall() {
+        let region = region_with_thin_walls(
+            &[(IVec2::ZERO, CompassQuadrant::South)],
+            IVec2::new(0, -1),
+            IVec2::ZERO,
+        );
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![
+                Wall::Edge(IVec2::new(0, -1), CompassQuadrant::South),
+                Wall::Edge(IVec2::new(0, 0), CompassQuadrant::North),
+                Wall::Corner(IVec2::new(0, 0)),
+                Wall::Corner(IVec2::new(1, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_walls_thin_wall_straight() {
+        let region = region_with_thin_walls(
+            &[
+                (IVec2::new(0, 0), CompassQuadrant::West),
+                (IVec2::new(0, 1), CompassQuadrant::West),
+            ],
+            IVec2::ZERO,
+            IVec2::new(0, 1),
+        );
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![
+                Wall::Edge(IVec2::new(0, 0), CompassQuadrant::East),
+                Wall::Edge(IVec2::new(0, 1), CompassQuadrant::East),
+                Wall::Corner(IVec2::new(0, 0)),
+                Wall::Corner(IVec2::new(0, 2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_walls_thin_wall_against_solid() {
+        let region = TileRegion::from_fn(
+            Entity::PLACEHOLDER,
+            IVec2::ZERO,
+            IVec2::ZERO,
+            |tile| {
+                if tile == IVec2::NEG_X {
+                    TileShape::Full
+                } else {
+                    TileShape::Empty
+                }
+            },
+            |tile, side| tile == IVec2::ZERO && side == CompassQuadrant::South,
+            |_, _| false,
+        );
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![
+                Wall::Edge(IVec2::ZERO, CompassQuadrant::North),
+                Wall::Edge(IVec2::ZERO, CompassQuadrant::East),
+                Wall::Corner(IVec2::new(0, 0)),
+                Wall::Corner(IVec2::new(1, 0)),
+                Wall::Corner(IVec2::new(0, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_walls_blocked_edge() {
+        let region = region_with_blocked_edges(
+            &[
+                (IVec2::new(0, -1), CompassQuadrant::North),
+                (IVec2::new(1, -1), CompassQuadrant::North),
+            ],
+            IVec2::new(0, -1),
+            IVec2::new(1, 0),
+        );
+        assert_eq!(
+            region.walls().collect::<Vec<_>>(),
+            vec![
+                Wall::Edge(IVec2::new(0, -1), CompassQuadrant::South),
+                Wall::Edge(IVec2::new(1, -1), CompassQuadrant::South),
+            ]
+        );
+    }
+
+    #[test]
+    fn shape_overlaps_circle() {
+        assert!(TileShape::Full.overlaps_circle(Vec2::new(0.5, 0.5), 0.1));
+        assert!(TileShape::Full.overlaps_circle(Vec2::new(-0.05, 0.5), 0.1));
+        assert!(!TileShape::Full.overlaps_circle(Vec2::new(-0.15, 0.5), 0.1));
+        assert!(!TileShape::HalfSouth.overlaps_circle(Vec2::new(0.5, 0.75), 0.2));
+        assert!(TileShape::HalfSouth.overlaps_circle(Vec2::new(0.5, 0.65), 0.2));
+        assert!(!TileShape::DiagonalSouthWest.overlaps_circle(Vec2::new(0.7, 0.7), 0.2));
+        assert!(TileShape::DiagonalSouthWest.overlaps_circle(Vec2::new(0.6, 0.6), 0.2));
+        assert!(!TileShape::Empty.overlaps_circle(Vec2::new(0.5, 0.5), 1.0));
+    }
+
     #[test]
     fn update_insert_neighborhood() {
         let mut world = World::new();
@@ -254,9 +1403,10 @@ mod tests {
             agent,
             old: None,
             new: Some(center),
+            reach: 1,
         });
 
-        assert_neighborhood(&index, center, agent);
+        assert_neighborhood(&index, center, agent, 1);
     }
 
     #[test]
@@ -271,14 +1421,16 @@ mod tests {
             agent,
             old: None,
             new: Some(center),
+            reach: 1,
         });
         index.update(&TileChanged {
             agent,
             old: Some(center),
             new: None,
+            reach: 1,
         });
 
-        for tile in center.neighborhood() {
+        for tile in center.neighborhood(1) {
             assert!(
                 !index.get(tile).contains(&agent),
                 "expected {:?} to be cleared",
@@ -299,14 +1451,16 @@ mod tests {
             agent,
             old: None,
             new: Some(center),
+            reach: 1,
         });
         index.update(&TileChanged {
             agent,
             old: Some(center),
             new: Some(center),
+            reach: 1,
         });
 
-        assert_neighborhood(&index, center, agent);
+        assert_neighborhood(&index, center, agent, 1);
     }
 
     #[test]
@@ -373,14 +1527,16 @@ mod tests {
             agent,
             old: None,
             new: Some(old),
+            reach: 1,
         });
         index.update(&TileChanged {
             agent,
             old: Some(old),
             new: Some(new),
+            reach: 1,
         });
 
-        for tile in old.neighborhood() {
+        for tile in old.neighborhood(1) {
             assert!(
                 !index.get(tile).contains(&agent),
                 "expected {:?} to be cleared (layer1)",
@@ -388,10 +1544,290 @@ mod tests {
             );
         }
 
-        assert_neighborhood(&index, new, agent);
+        assert_neighborhood(&index, new, agent, 1);
+    }
+
+    #[test]
+    fn update_insert_neighborhood_large() {
+        let mut world = World::new();
+        let layer = world.spawn(()).id();
+        let agent = world.spawn(()).id();
+
+        let mut index = TileIndex::default();
+        let center = Tile::new(layer, 0, 0);
+        index.update(&TileChanged {
+            agent,
+            old: None,
+            new: Some(center),
+            reach: 3,
+        });
+
+        assert_neighborhood(&index, center, agent, 3);
+    }
+
+    #[test]
+    fn update_remove_neighborhood_large() {
+        let mut world = World::new();
+        let layer = world.spawn(()).id();
+        let agent = world.spawn(()).id();
+
+        let mut index = TileIndex::default();
+        let center = Tile::new(layer, 0, 0);
+        index.update(&TileChanged {
+            agent,
+            old: None,
+            new: Some(center),
+            reach: 2,
+        });
+        index.update(&TileChanged {
+            agent,
+            old: Some(center),
+            new: None,
+            reach: 2,
+        });
+
+        assert!(index.index.is_empty());
+    }
+
+    #[test]
+    fn update_move_large_cardinal() {
+        assert_move_with_reach(IVec2::new(0, 0), IVec2::new(1, 0), 2);
+        assert_move_with_reach(IVec2::new(0, 0), IVec2::new(0, -1), 3);
+    }
+
+    #[test]
+    fn update_move_large_diagonal() {
+        assert_move_with_reach(IVec2::new(0, 0), IVec2::new(-1, 1), 2);
+        assert_move_with_reach(IVec2::new(0, 0), IVec2::new(1, -1), 3);
+    }
+
+    #[test]
+    fn update_jump_large() {
+        assert_move_with_reach(IVec2::new(0, 0), IVec2::new(3, -1), 2);
+        assert_move_with_reach(IVec2::new(0, 0), IVec2::new(-7, 9), 2);
+    }
+
+    #[test]
+    fn query_deduplicated() {
+        let mut world = World::new();
+        let layer = world.spawn(()).id();
+        let near = world.spawn(()).id();
+        let large = world.spawn(()).id();
+        let far = world.spawn(()).id();
+
+        let agents = [
+            (near, IVec2::new(0, 0), 1),
+            (large, IVec2::new(3, 1), 2),
+            (far, IVec2::new(6, 0), 1),
+        ];
+        let mut index = TileIndex::default();
+        for (agent, tile, reach) in agents {
+            index.update(&TileChanged {
+                agent,
+                old: None,
+                new: Some(Tile(layer, tile)),
+                reach,
+            });
+        }
+
+        let found: Vec<Entity> = index
+            .query(layer, IVec2::new(-1, -1), IVec2::new(2, 2), |id| {
+                let &(_, tile, _) = agents.iter().find(|&&(agent, ..)| agent == id)?;
+                Some(((), tile))
+            })
+            .map(|(id, ())| id)
+            .collect();
+        assert_eq!(found.len(), 2);
+        assert!(found.contains(&near));
+        assert!(found.contains(&large));
+    }
+
+    #[test]
+    fn traverse_diagonal() {
+        let layer = Layer::default();
+        let tiles: Vec<_> = Tile::traverse(
+            Entity::PLACEHOLDER,
+            &layer,
+            Vec2::new(0.5, 0.25),
+            Vec2::new(1.0, 1.0).normalize(),
+            2.2,
+        )
+        .map(|(tile, entered)| (tile.tile(), entered))
+        .collect();
+
+        assert_eq!(tiles[0].0, IVec2::new(0, 0));
+        assert_eq!(tiles[1].0, IVec2::new(1, 0));
+        assert_eq!(tiles[2].0, IVec2::new(1, 1));
+        assert_eq!(tiles[3].0, IVec2::new(2, 1));
+        assert_relative_eq!(tiles[0].1, 0.0);
+        assert_relative_eq!(tiles[1].1, 0.5 * 2f32.sqrt());
+        assert_relative_eq!(tiles[2].1, 0.75 * 2f32.sqrt());
+        assert_relative_eq!(tiles[3].1, 1.5 * 2f32.sqrt());
+        assert_eq!(tiles.len(), 4);
+    }
+
+    #[test]
+    fn traverse_negative_axis() {
+        let layer = Layer::new(2.0);
+        let tiles: Vec<_> = Tile::traverse(
+            Entity::PLACEHOLDER,
+            &layer,
+            Vec2::new(1.0, 1.0),
+            Vec2::NEG_X,
+            3.0,
+        )
+        .map(|(tile, entered)| (tile.tile(), entered))
+        .collect();
+
+        assert_eq!(
+            tiles,
+            vec![
+                (IVec2::new(0, 0), 0.0),
+                (IVec2::new(-1, 0), 1.0),
+                (IVec2::new(-2, 0), 3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn traverse_not_finite() {
+        let layer = Layer::default();
+        let tiles = Tile::traverse(
+            Entity::PLACEHOLDER,
+            &layer,
+            Vec2::new(f32::INFINITY, 0.5),
+            Vec2::X,
+            4.0,
+        );
+
+        assert!(tiles.count() <= 11);
+    }
+
+    #[test]
+    fn traverse_segment() {
+        let layer = Layer::default();
+        let tiles: Vec<_> = Tile::traverse_segment(
+            Entity::PLACEHOLDER,
+            &layer,
+            Vec2::new(0.5, 0.5),
+            Vec2::new(2.5, 1.5),
+        )
+        .map(|(tile, entered, start, end)| (tile.tile(), entered, start, end))
+        .collect();
+
+        let length = Vec2::new(2.0, 1.0).length();
+        assert_eq!(tiles.len(), 4);
+        assert_eq!(tiles[0].0, IVec2::new(0, 0));
+        assert_eq!(tiles[1].0, IVec2::new(1, 0));
+        assert_eq!(tiles[2].0, IVec2::new(1, 1));
+        assert_eq!(tiles[3].0, IVec2::new(2, 1));
+        assert_relative_eq!(tiles[0].2, Vec2::new(0.5, 0.5));
+        assert_relative_eq!(tiles[0].3, Vec2::new(1.0, 0.75));
+        assert_relative_eq!(tiles[1].1, length * 0.25);
+        assert_relative_eq!(tiles[2].2, Vec2::new(1.5, 1.0));
+        assert_relative_eq!(tiles[3].3, Vec2::new(2.5, 1.5));
+    }
+
+    #[test]
+    fn traverse_segment_empty() {
+        let layer = Layer::default();
+        let tiles: Vec<_> = Tile::traverse_segment(
+            Entity::PLACEHOLDER,
+            &layer,
+            Vec2::new(0.5, 0.5),
+            Vec2::new(0.5, 0.5),
+        )
+        .map(|(tile, ..)| tile.tile())
+        .collect();
+        assert_eq!(tiles, vec![IVec2::new(0, 0)]);
+
+        let tiles = Tile::traverse_segment(
+            Entity::PLACEHOLDER,
+            &layer,
+            Vec2::new(0.5, 0.5),
+            Vec2::new(f32::INFINITY, 0.5),
+        );
+        assert_eq!(tiles.count(), 1);
+    }
+
+    #[test]
+    fn reach() {
+        assert_eq!(Tile::reach(0.0, 1.0), 1);
+        assert_eq!(Tile::reach(0.3, 1.0), 1);
+        assert_eq!(Tile::reach(0.5, 1.0), 1);
+        assert_eq!(Tile::reach(0.6, 1.0), 2);
+        assert_eq!(Tile::reach(1.5, 1.0), 3);
+        assert_eq!(Tile::reach(1.5, 0.5), 2);
+    }
+
+    fn region(solid: &[IVec2], min: IVec2, max: IVec2) -> TileRegion {
+        TileRegion::from_fn(
+            Entity::PLACEHOLDER,
+            min,
+            max,
+            |tile| {
+                if solid.contains(&tile) {
+                    TileShape::Full
+                } else {
+                    TileShape::Empty
+                }
+            },
+            |_, _| false,
+            |_, _| false,
+        )
+    }
+
+    fn region_with_shapes(shapes: &[(IVec2, TileShape)], min: IVec2, max: IVec2) -> TileRegion {
+        TileRegion::from_fn(
+            Entity::PLACEHOLDER,
+            min,
+            max,
+            |tile| {
+                shapes
+                    .iter()
+                    .find(|&&(shape_tile, _)| shape_tile == tile)
+                    .map_or(TileShape::Empty, |&(_, shape)| shape)
+            },
+            |_, _| false,
+            |_, _| false,
+        )
+    }
+
+    fn region_with_thin_walls(
+        thin_walls: &[(IVec2, CompassQuadrant)],
+        min: IVec2,
+        max: IVec2,
+    ) -> TileRegion {
+        TileRegion::from_fn(
+            Entity::PLACEHOLDER,
+            min,
+            max,
+            |_| TileShape::Empty,
+            |tile, side| thin_walls.contains(&(tile, side)),
+            |_, _| false,
+        )
+    }
+
+    fn region_with_blocked_edges(
+        blocked: &[(IVec2, CompassQuadrant)],
+        min: IVec2,
+        max: IVec2,
+    ) -> TileRegion {
+        TileRegion::from_fn(
+            Entity::PLACEHOLDER,
+            min,
+            max,
+            |_| TileShape::Empty,
+            |_, _| false,
+            |tile, side| blocked.contains(&(tile, side)),
+        )
     }
 
     fn assert_move(old: IVec2, new: IVec2) {
+        assert_move_with_reach(old, new, 1);
+    }
+
+    fn assert_move_with_reach(old: IVec2, new: IVec2, reach: i32) {
         let mut world = World::new();
         let layer = world.spawn(()).id();
         let agent = world.spawn(()).id();
@@ -403,22 +1839,33 @@ mod tests {
             agent,
             old: None,
             new: Some(old),
+            reach,
         });
         index.update(&TileChanged {
             agent,
             old: Some(old),
             new: Some(new),
+            reach,
         });
 
-        assert_neighborhood(&index, new, agent);
+        assert_neighborhood(&index, new, agent, reach);
+        assert_eq!(
+            index
+                .index
+                .values()
+                .map(|agents| agents.len())
+                .sum::<usize>(),
+            ((2 * reach + 1) * (2 * reach + 1)) as usize,
+            "stale tiles left in index"
+        );
     }
 
-    fn assert_neighborhood(index: &TileIndex, center: Tile, agent: Entity) {
-        for x in center.x() - 2..=center.x() + 2 {
-            for y in center.y() - 2..=center.y() + 2 {
+    fn assert_neighborhood(index: &TileIndex, center: Tile, agent: Entity, reach: i32) {
+        for x in center.x() - reach - 1..=center.x() + reach + 1 {
+            for y in center.y() - reach - 1..=center.y() + reach + 1 {
                 let tile = Tile::new(center.layer(), x, y);
                 let agents = index.get(tile);
-                if tile.1.chebyshev_distance(center.1) > 1 {
+                if tile.1.chebyshev_distance(center.1) > reach as u32 {
                     assert!(
                         !agents.contains(&agent),
                         "did not expect {:?} to contain agent",
@@ -0,0 +1,254 @@
+use bevy::{
+    ecs::system::{StaticSystemParam, SystemParamItem},
+    prelude::*,
+};
+
+use crate::{
+    Agent, Layer,
+    tile::{Tile, TileIndex, TileMap},
+};
+
+// The maximum distance, in tiles, an agent is moved to find a free position.
+const MAX_SEARCH_DISTANCE: i32 = 8;
+
+/// A message which must be written when tiles of a [`TileMap`] are changed, for example when a door closes.
+///
+/// Agents overlapping tiles which became solid are moved to the nearest position free of solid tiles, if there is one
+/// within a few tiles. When a thin wall or blocked edge changes, either of the tiles on each side of it should be
+/// included, so the walls cached in a [`TileCache`](crate::TileCache) are refreshed. Agents are never moved by edge
+/// changes, so an agent overlapping a new thin wall is left where it is.
+#[derive(Clone, Debug, Message, PartialEq, Eq)]
+pub struct TileMapChanged {
+    /// The layer containing the changed tiles.
+    pub layer: Entity,
+    /// The coordinates of the changed tiles.
+    pub tiles: Vec<IVec2>,
+}
+
+pub(crate) fn revalidate<T>(
+    index: Res<TileIndex>,
+    mut reader: MessageReader<TileMapChanged>,
+    mut agents: Query<(&Agent, &mut Transform, &ChildOf)>,
+    layers: Query<&Layer>,
+    map: StaticSystemParam<T>,
+    mut affected: Local<Vec<Entity>>,
+) where
+    T: TileMap,
+    for<'w, 's> SystemParamItem<'w, 's, T>: TileMap,
+{
+    for event in reader.read() {
+        affected.clear();
+        for &tile in &event.tiles {
+            // Open tiles, including those with changed edges, never need agents moved out of them.
+            if !map.is_solid(event.layer, tile) {
+                continue;
+            }
+
+            // Agents are indexed at their position in the previous step, so also check the surrounding tiles in case
+            // they have moved into this one since.
+            for query_tile in Tile::new(event.layer, tile.x, tile.y).neighborhood(1) {
+                affected.extend_from_slice(index.get(query_tile));
+            }
+        }
+        affected.sort_unstable();
+        affected.dedup();
+
+        let Ok(layer) = layers.get(event.layer) else {
+            continue;
+        };
+
+        for &id in affected.iter() {
+            let Ok((agent, mut transform, parent)) = agents.get_mut(id) else {
+                continue;
+            };
+
+            if parent.0 != event.layer || !agent.collision_groups().collides_with_walls() {
+                continue;
+            }
+
+            let position = transform.translation.xy();
+            if !overlaps_solid(&*map, event.layer, layer, agent.radius(), position) {
+                continue;
+            }
+
+            if let Some(free) =
+                nearest_free_position(&*map, event.layer, layer, agent.radius(), position)
+            {
+                transform.translation.x = free.x;
+                transform.translation.y = free.y;
+            }
+        }
+    }
+}
+
+// Returns `true` if a circle overlaps the solid part of any tile.
+fn overlaps_solid(
+    map: &impl TileMap,
+    layer_id: Entity,
+    layer: &Layer,
+    radius: f32,
+    position: Vec2,
+) -> bool {
+    let min = Tile::floor(layer_id, position - radius, layer.scale()).tile();
+    let max = Tile::floor(layer_id, position + radius, layer.scale()).tile();
+    Tile::rect(layer_id, min, max).any(|tile| {
+        if !map.is_solid(layer_id, tile.tile()) {
+            return false;
+        }
+
+        let center = position * layer.scale() - tile.tile().as_vec2();
+        map.shape(layer_id, tile.tile())
+            .overlaps_circle(center, radius * layer.scale())
+    })
+}
+
+// Returns the nearest position to the given one where a circle doesn't overlap any solid tiles.
+fn nearest_free_position(
+    map: &impl TileMap,
+    layer_id: Entity,
+    layer: &Layer,
+    radius: f32,
+    position: Vec2,
+) -> Option<Vec2> {
+    let tile_size = layer.tile_size();
+    let center = Tile::floor(layer_id, position, layer.scale());
+
+    let mut nearest: Option<(f32, Vec2)> = None;
+    for distance in 0..=MAX_SEARCH_DISTANCE {
+        // Every tile in this ring is at least this far from the starting position.
+        if nearest.is_some_and(|(nearest, _)| nearest < (distance - 1) as f32 * tile_size) {
+            break;
+        }
+
+        for tile in center.neighborhood(distance) {
+            if tile.tile().chebyshev_distance(center.tile()) != distance as u32
+                || map.is_solid(layer_id, tile.tile())
+            {
+                continue;
+            }
+
+            // Keep the candidate away from the solid tiles bordering this one.
+            let margin = |offset: IVec2| {
+                if map.is_solid(layer_id, tile.tile() + offset) {
+                    radius
+                } else {
+                    0.0
+                }
+            };
+            let tile_min = tile.tile().as_vec2() * tile_size;
+            let min = tile_min + Vec2::new(margin(IVec2::NEG_X), margin(IVec2::NEG_Y));
+            let max =
+                tile_min + Vec2::splat(tile_size) - Vec2::new(margin(IVec2::X), margin(IVec2::Y));
+            if min.x > max.x || min.y > max.y {
+                continue;
+            }
+
+            let candidate = position.clamp(min, max);
+            let candidate_distance = candidate.distance(position);
+            if nearest.is_none_or(|(nearest, _)| candidate_distance < nearest)
+                && !overlaps_solid(map, layer_id, layer, radius, candidate)
+            {
+                nearest = Some((candidate_distance, candidate));
+            }
+        }
+    }
+
+    nearest.map(|(_, position)| position)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use bevy::ecs::system::SystemParam;
+
+    use super::*;
+
+    #[derive(SystemParam)]
+    struct Block;
+
+    impl TileMap for Block {
+        fn is_solid(&self, _: Entity, tile: IVec2) -> bool {
+            tile.x >= 0 && tile.x < 3 && tile.y >= 0 && tile.y < 3
+        }
+    }
+
+    #[test]
+    fn overlaps_solid_edge() {
+        let layer = Layer::default();
+        assert!(overlaps_solid(
+            &Block,
+            Entity::PLACEHOLDER,
+            &layer,
+            0.2,
+            Vec2::new(-0.1, 0.5)
+        ));
+        assert!(!overlaps_solid(
+            &Block,
+            Entity::PLACEHOLDER,
+            &layer,
+            0.2,
+            Vec2::new(-0.2, 0.5)
+        ));
+    }
+
+    #[test]
+    fn overlaps_solid_corner() {
+        let layer = Layer::default();
+        assert!(overlaps_solid(
+            &Block,
+            Entity::PLACEHOLDER,
+            &layer,
+            0.2,
+            Vec2::new(-0.1, -0.1)
+        ));
+        assert!(!overlaps_solid(
+            &Block,
+            Entity::PLACEHOLDER,
+            &layer,
+            0.2,
+            Vec2::new(-0.15, -0.15)
+        ));
+    }
+
+    #[test]
+    fn nearest_free_position_edge() {
+        let layer = Layer::default();
+        let position = nearest_free_position(
+            &Block,
+            Entity::PLACEHOLDER,
+            &layer,
+            0.2,
+            Vec2::new(0.4, 1.5),
+        )
+        .unwrap();
+        assert_relative_eq!(position, Vec2::new(-0.2, 1.5));
+    }
+
+    #[test]
+    fn nearest_free_position_center() {
+        let layer = Layer::default();
+        let position = nearest_free_position(
+            &Block,
+            Entity::PLACEHOLDER,
+            &layer,
+            0.2,
+            Vec2::new(1.5, 1.9),
+        )
+        .unwrap();
+        assert_relative_eq!(position, Vec2::new(1.5, 3.2));
+    }
+
+    #[test]
+    fn nearest_free_position_large_agent() {
+        let layer = Layer::default();
+        let position = nearest_free_position(
+            &Block,
+            Entity::PLACEHOLDER,
+            &layer,
+            0.8,
+            Vec2::new(0.4, 1.5),
+        )
+        .unwrap();
+        assert_relative_eq!(position, Vec2::new(-0.8, 1.5));
+    }
+}
@@ -2,10 +2,26 @@ use std::time::Duration;
 
 use approx::assert_relative_eq;
 use bevy::{
+    ecs::system::{RunSystemOnce, SystemParam},
+    math::CompassQuadrant,
     prelude::*,
     time::{TimePlugin, TimeUpdateStrategy},
 };
-use jostle::{Agent, JostlePlugin, Layer, Velocity};
+use jostle::{
+    Agent, AgentCollided, CastHit, CastQuery, CollisionGroups, CollisionTarget, JostlePlugin,
+    Layer, LayerGrid, Mass, Obstacle, Sensor, SensorEntered, SensorExited, TileCache, TileGrid,
+    TileMap, TileMapChanged, TileShape, Velocity,
+};
+
+/// A tile map where all tiles below `y = 0` are solid.
+#[derive(SystemParam)]
+struct Floor;
+
+impl TileMap for Floor {
+    fn is_solid(&self, _: Entity, tile: IVec2) -> bool {
+        tile.y < 0
+    }
+}
 
 #[test]
 fn static_agent() {
@@ -183,33 +199,1490 @@ fn colliding_agent_oblique() {
     assert_relative_eq!(velocity2, Vec2::new(0.0, 0.3));
 }
 
-fn make_app() -> App {
-    let mut app = App::new();
-    app.add_plugins((TransformPlugin, TimePlugin, JostlePlugin::<()>::default()));
-    app.finish();
-    app.cleanup();
+#[test]
+fn colliding_agent_messages() {
+    let mut app = make_app();
 
-    app.insert_resource(Time::<Fixed>::from_duration(Duration::from_secs(1)));
-    app.insert_resource(Time::<Virtual>::from_max_delta(Duration::MAX));
-    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent1 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            Velocity(Vec2::new(0.5, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent2 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(1.0, 0.0, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
 
-    // Initialize time resource
-    app.world_mut()
-        .resource_mut::<Time<Real>>()
-        .update_with_duration(Duration::ZERO);
+    advance_time(&mut app, 1.0);
+    let collisions = update_get_collisions(&mut app);
+    assert!(collisions.is_empty());
 
-    app
+    let collisions = update_get_collisions(&mut app);
+    assert_eq!(collisions.len(), 1);
+    assert_eq!(collisions[0].agent, agent1);
+    assert_eq!(collisions[0].other, CollisionTarget::Agent(agent2));
+    assert_relative_eq!(collisions[0].normal, Vec2::new(-1.0, 0.0));
+    assert_relative_eq!(collisions[0].time_of_impact, 0.2, epsilon = 1e-6);
+    assert_relative_eq!(collisions[0].relative_speed, 0.5);
 }
 
-fn advance_time(app: &mut App, secs: f32) {
-    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
-        secs,
-    )));
+#[test]
+fn sliding_agent_wall() {
+    let mut app = make_app_with_map::<Floor>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, 0.5, 0.0),
+            Velocity(Vec2::new(1.0, -1.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(1.0, 0.35));
+    assert_relative_eq!(velocity, Vec2::new(1.0, 0.0));
 }
 
-fn get_agent(app: &App, id: Entity) -> (Vec2, Vec2) {
-    (
-        app.world().get::<Transform>(id).unwrap().translation.xy(),
-        app.world().get::<Velocity>(id).unwrap().0,
-    )
+#[test]
+fn sliding_agent_wall_single_iteration() {
+    let mut app = make_app_with_map::<Floor>();
+
+    let layer = app
+        .world_mut()
+        .spawn(Layer::default().with_collision_iterations(1))
+        .id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, 0.5, 0.0),
+            Velocity(Vec2::new(1.0, -1.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(0.65, 0.35));
+    assert_relative_eq!(velocity, Vec2::new(1.0, 0.0));
+}
+
+#[test]
+fn sliding_agent_agent() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent1 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.25),
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            Velocity(Vec2::new(1.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    app.world_mut().spawn((
+        Agent::new(0.25),
+        Transform::from_xyz(0.6, 0.3, 0.0),
+        ChildOf(layer),
+    ));
+
+    advance_time(&mut app, 1.5);
+    let collisions = update_get_collisions(&mut app);
+
+    assert_eq!(collisions.len(), 1);
+    assert_relative_eq!(collisions[0].normal, Vec2::new(-0.8, -0.6), epsilon = 1e-6);
+    assert_relative_eq!(collisions[0].time_of_impact, 0.2, epsilon = 1e-6);
+
+    let (position, velocity) = get_agent(&app, agent1);
+    assert_relative_eq!(position, Vec2::new(0.244, -0.192), epsilon = 1e-6);
+    assert_relative_eq!(velocity, Vec2::new(0.36, -0.48), epsilon = 1e-6);
+}
+
+#[test]
+fn colliding_large_agents() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent1 = app
+        .world_mut()
+        .spawn((
+            Agent::new(1.5),
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            Velocity(Vec2::new(2.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent2 = app
+        .world_mut()
+        .spawn((
+            Agent::new(1.5),
+            Transform::from_xyz(4.0, 0.0, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+
+    let (position1, velocity1) = get_agent(&app, agent1);
+    assert_relative_eq!(position1, Vec2::new(0.5, 0.0));
+    assert_relative_eq!(velocity1, Vec2::new(0.0, 0.0));
+    let (position2, _) = get_agent(&app, agent2);
+    assert_relative_eq!(position2, Vec2::new(4.0, 0.0));
+}
+
+#[test]
+fn colliding_large_and_small_agents() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let small = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            Velocity(Vec2::new(1.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let large = app
+        .world_mut()
+        .spawn((
+            Agent::new(2.0),
+            Transform::from_xyz(2.7, 0.0, 0.0),
+            Velocity(Vec2::new(-1.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+
+    let (position, velocity) = get_agent(&app, small);
+    assert_relative_eq!(position, Vec2::new(0.125, 0.0));
+    assert_relative_eq!(velocity, Vec2::new(0.0, 0.0));
+    let (position, velocity) = get_agent(&app, large);
+    assert_relative_eq!(position, Vec2::new(2.575, 0.0));
+    assert_relative_eq!(velocity, Vec2::new(0.0, 0.0));
+}
+
+#[test]
+fn colliding_large_agent_wall() {
+    let mut app = make_app_with_map::<Floor>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(1.5),
+            Transform::from_xyz(0.5, 2.5, 0.0),
+            Velocity(Vec2::new(0.0, -2.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(0.5, 2.0));
+    assert_relative_eq!(velocity, Vec2::new(0.0, 0.0));
+}
+
+#[test]
+fn colliding_agent_corner() {
+    let mut app = make_app_with_map::<Block>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.5),
+            Transform::from_xyz(-0.6, -0.4, 0.0),
+            Velocity(Vec2::new(1.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    let collisions = update_get_collisions(&mut app);
+
+    assert_eq!(collisions.len(), 1);
+    assert_eq!(collisions[0].other, CollisionTarget::Wall);
+    assert_relative_eq!(collisions[0].normal, Vec2::new(-0.6, -0.8), epsilon = 1e-6);
+    assert_relative_eq!(collisions[0].time_of_impact, 0.3, epsilon = 1e-6);
+
+    let (_, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(velocity, Vec2::new(0.64, -0.48), epsilon = 1e-6);
+}
+
+#[test]
+fn sliding_agent_past_wall_end() {
+    let mut app = make_app_with_map::<Block>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, 1.2, 0.0),
+            Velocity(Vec2::new(1.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    let collisions = update_get_collisions(&mut app);
+
+    assert!(collisions.is_empty());
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(1.0, 1.2));
+    assert_relative_eq!(velocity, Vec2::new(1.0, 0.0));
+}
+
+/// A tile map with a single solid tile at the origin.
+#[derive(SystemParam)]
+struct Block;
+
+impl TileMap for Block {
+    fn is_solid(&self, _: Entity, tile: IVec2) -> bool {
+        tile == IVec2::ZERO
+    }
+}
+
+#[test]
+fn moving_agent_past_corner() {
+    let mut app = make_app_with_map::<Block>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(1.5, 0.99, 0.0),
+            Velocity(Vec2::new(-1.0, 2.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    let collisions = update_get_collisions(&mut app);
+
+    assert!(collisions.is_empty());
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(1.0, 1.99));
+    assert_relative_eq!(velocity, Vec2::new(-1.0, 2.0));
+}
+
+#[test]
+fn ghost_agent_passing_through_agent() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let ghost = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2)
+                .with_collision_groups(CollisionGroups::new(0b1, CollisionGroups::WALLS)),
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            Velocity(Vec2::new(0.5, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(1.0, 0.0, 0.0),
+            Velocity(Vec2::new(-0.5, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    let collisions = update_get_collisions(&mut app);
+    assert!(collisions.is_empty());
+
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position1, velocity1) = get_agent(&app, ghost);
+    assert_relative_eq!(position1, Vec2::new(0.5, 0.0));
+    assert_relative_eq!(velocity1, Vec2::new(0.5, 0.0));
+    let (position2, velocity2) = get_agent(&app, agent);
+    assert_relative_eq!(position2, Vec2::new(0.5, 0.0));
+    assert_relative_eq!(velocity2, Vec2::new(-0.5, 0.0));
+}
+
+#[test]
+fn agent_passing_through_wall() {
+    let mut app = make_app_with_map::<Floor>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2).with_collision_groups(CollisionGroups::new(
+                CollisionGroups::ALL,
+                !CollisionGroups::WALLS,
+            )),
+            Transform::from_xyz(0.5, 0.5, 0.0),
+            Velocity(Vec2::new(0.0, -1.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    let collisions = update_get_collisions(&mut app);
+    assert!(collisions.is_empty());
+
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(0.5, -0.5));
+    assert_relative_eq!(velocity, Vec2::new(0.0, -1.0));
+}
+
+#[test]
+fn agent_passing_through_sensor() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let sensor = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.5),
+            Sensor,
+            Transform::from_xyz(1.5, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.1),
+            Transform::from_xyz(0.0, 0.5, 0.0),
+            Velocity(Vec2::new(1.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.0);
+    let (entered, exited) = update_get_sensor_messages(&mut app);
+    assert_eq!(entered, vec![]);
+    assert_eq!(exited, vec![]);
+
+    let (entered, exited) = update_get_sensor_messages(&mut app);
+    assert_eq!(entered, vec![SensorEntered { sensor, agent }]);
+    assert_eq!(exited, vec![]);
+
+    let (entered, exited) = update_get_sensor_messages(&mut app);
+    assert_eq!(entered, vec![]);
+    assert_eq!(exited, vec![]);
+
+    let (entered, exited) = update_get_sensor_messages(&mut app);
+    assert_eq!(entered, vec![]);
+    assert_eq!(exited, vec![SensorExited { sensor, agent }]);
+
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(3.0, 0.5));
+    assert_relative_eq!(velocity, Vec2::new(1.0, 0.0));
+}
+
+#[test]
+fn agent_despawned_in_sensor() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let sensor = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.5),
+            Sensor,
+            Transform::from_xyz(0.5, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.1),
+            Transform::from_xyz(0.5, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.0);
+    let (entered, exited) = update_get_sensor_messages(&mut app);
+    assert_eq!(entered, vec![SensorEntered { sensor, agent }]);
+    assert_eq!(exited, vec![]);
+
+    app.world_mut().despawn(agent);
+
+    let (entered, exited) = update_get_sensor_messages(&mut app);
+    assert_eq!(entered, vec![]);
+    assert_eq!(exited, vec![SensorExited { sensor, agent }]);
+}
+
+#[test]
+fn sensor_filtered_by_collision_groups() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    app.world_mut().spawn((
+        Agent::new(0.5).with_collision_groups(CollisionGroups::new(0b01, 0b01)),
+        Sensor,
+        Transform::from_xyz(0.5, 0.5, 0.0),
+        ChildOf(layer),
+    ));
+    app.world_mut().spawn((
+        Agent::new(0.1).with_collision_groups(CollisionGroups::new(0b10, CollisionGroups::ALL)),
+        Transform::from_xyz(0.5, 0.5, 0.0),
+        ChildOf(layer),
+    ));
+
+    advance_time(&mut app, 1.0);
+    let (entered, exited) = update_get_sensor_messages(&mut app);
+    assert_eq!(entered, vec![]);
+    assert_eq!(exited, vec![]);
+}
+
+#[test]
+fn colliding_agent_obstacle() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let obstacle = app
+        .world_mut()
+        .spawn((
+            Obstacle::new(0.3),
+            Transform::from_xyz(1.5, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.0, 0.5, 0.0),
+            Velocity(Vec2::new(2.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    let collisions = update_get_collisions(&mut app);
+    assert_eq!(collisions.len(), 1);
+    assert_eq!(collisions[0].agent, agent);
+    assert_eq!(collisions[0].other, CollisionTarget::Obstacle(obstacle));
+    assert_relative_eq!(collisions[0].normal, Vec2::new(-1.0, 0.0));
+    assert_relative_eq!(collisions[0].time_of_impact, 0.5);
+    assert_relative_eq!(collisions[0].relative_speed, 2.0);
+
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(1.0, 0.5));
+    assert_relative_eq!(velocity, Vec2::new(0.0, 0.0));
+    assert_relative_eq!(
+        app.world()
+            .get::<Transform>(obstacle)
+            .unwrap()
+            .translation
+            .xy(),
+        Vec2::new(1.5, 0.5)
+    );
+}
+
+#[test]
+fn colliding_agent_moved_obstacle() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let obstacle = app
+        .world_mut()
+        .spawn((
+            Obstacle::new(0.3),
+            Transform::from_xyz(1.5, 10.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(-2.0, 0.5, 0.0),
+            Velocity(Vec2::new(2.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.0);
+    app.update();
+
+    app.world_mut()
+        .get_mut::<Transform>(obstacle)
+        .unwrap()
+        .translation = Vec3::new(1.5, 0.5, 0.0);
+
+    let collisions = update_get_collisions(&mut app);
+    assert_eq!(collisions.len(), 1);
+    assert_eq!(collisions[0].other, CollisionTarget::Obstacle(obstacle));
+
+    app.update();
+
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(1.0, 0.5));
+    assert_relative_eq!(velocity, Vec2::new(0.0, 0.0));
+}
+
+#[test]
+fn separating_overlapping_agents() {
+    let mut app = make_app();
+
+    let layer = app
+        .world_mut()
+        .spawn(Layer::default().with_separation(0.5))
+        .id();
+    let agent1 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.0, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent2 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.2, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position1, _) = get_agent(&app, agent1);
+    assert_relative_eq!(position1, Vec2::new(-0.05, 0.5));
+    let (position2, _) = get_agent(&app, agent2);
+    assert_relative_eq!(position2, Vec2::new(0.25, 0.5));
+}
+
+#[test]
+fn separating_agent_wall() {
+    let mut app = make_app_with_map::<Floor>();
+
+    let layer = app
+        .world_mut()
+        .spawn(Layer::default().with_separation(1.0))
+        .id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, -0.3, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position, _) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(0.5, 0.2));
+}
+
+#[test]
+fn pushing_light_agent() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let heavy = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Mass(3.0),
+            Transform::from_xyz(0.0, 0.5, 0.0),
+            Velocity(Vec2::new(1.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let light = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Mass(1.0),
+            Transform::from_xyz(1.0, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position1, velocity1) = get_agent(&app, heavy);
+    assert_relative_eq!(position1, Vec2::new(0.9, 0.5));
+    assert_relative_eq!(velocity1, Vec2::new(0.75, 0.0));
+    let (position2, velocity2) = get_agent(&app, light);
+    assert_relative_eq!(position2, Vec2::new(1.3, 0.5));
+    assert_relative_eq!(velocity2, Vec2::new(0.75, 0.0));
+}
+
+#[test]
+fn pushing_agent_without_mass() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let heavy = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Mass(3.0),
+            Transform::from_xyz(0.0, 0.5, 0.0),
+            Velocity(Vec2::new(1.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let other = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(1.0, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position1, velocity1) = get_agent(&app, heavy);
+    assert_relative_eq!(position1, Vec2::new(0.6, 0.5));
+    assert_relative_eq!(velocity1, Vec2::new(0.0, 0.0));
+    let (position2, velocity2) = get_agent(&app, other);
+    assert_relative_eq!(position2, Vec2::new(1.0, 0.5));
+    assert_relative_eq!(velocity2, Vec2::new(0.0, 0.0));
+}
+
+#[test]
+fn pushing_agent_with_zero_mass() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent1 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Mass(0.0),
+            Transform::from_xyz(0.0, 0.5, 0.0),
+            Velocity(Vec2::new(1.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent2 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Mass(0.0),
+            Transform::from_xyz(1.0, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    // Agents without a positive mass are stopped as if they had no mass.
+    let (position1, velocity1) = get_agent(&app, agent1);
+    assert_relative_eq!(position1, Vec2::new(0.6, 0.5));
+    assert_relative_eq!(velocity1, Vec2::new(0.0, 0.0));
+    let (position2, velocity2) = get_agent(&app, agent2);
+    assert_relative_eq!(position2, Vec2::new(1.0, 0.5));
+    assert_relative_eq!(velocity2, Vec2::new(0.0, 0.0));
+}
+
+#[test]
+fn casting_ray_wall() {
+    let mut app = make_app_with_map::<Floor>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+
+    let hit = cast::<Floor>(&mut app, layer, Vec2::new(0.5, 2.0), 0.0, Dir2::NEG_Y, 10.0);
+    assert_eq!(
+        hit,
+        Some(CastHit {
+            target: CollisionTarget::Wall,
+            distance: 2.0,
+            point: Vec2::new(0.5, 0.0),
+            normal: Vec2::Y,
+        })
+    );
+
+    let hit = cast::<Floor>(&mut app, layer, Vec2::new(0.5, 2.0), 0.0, Dir2::NEG_Y, 1.5);
+    assert_eq!(hit, None);
+
+    let hit = cast::<Floor>(&mut app, layer, Vec2::new(0.5, 2.0), 0.0, Dir2::X, 10.0);
+    assert_eq!(hit, None);
+}
+
+#[test]
+fn casting_ray_invalid_distance() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+
+    for max_distance in [f32::INFINITY, f32::NAN, -1.0] {
+        let hit = cast::<()>(
+            &mut app,
+            layer,
+            Vec2::new(0.5, 2.0),
+            0.0,
+            Dir2::X,
+            max_distance,
+        );
+        assert_eq!(hit, None);
+    }
+}
+
+#[test]
+fn casting_ray_oblique() {
+    let mut app = make_app_with_map::<Floor>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+
+    let hit = cast::<Floor>(
+        &mut app,
+        layer,
+        Vec2::new(0.5, 1.5),
+        0.0,
+        Dir2::new(Vec2::new(3.0, -4.0)).unwrap(),
+        10.0,
+    )
+    .unwrap();
+    assert_eq!(hit.target, CollisionTarget::Wall);
+    assert_relative_eq!(hit.distance, 1.875);
+    assert_relative_eq!(hit.point, Vec2::new(1.625, 0.0));
+    assert_relative_eq!(hit.normal, Vec2::Y);
+}
+
+#[test]
+fn casting_circle_wall() {
+    let mut app = make_app_with_map::<Floor>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+
+    let hit = cast::<Floor>(
+        &mut app,
+        layer,
+        Vec2::new(0.5, 2.0),
+        0.25,
+        Dir2::NEG_Y,
+        10.0,
+    )
+    .unwrap();
+    assert_eq!(hit.target, CollisionTarget::Wall);
+    assert_relative_eq!(hit.distance, 1.75);
+    assert_relative_eq!(hit.point, Vec2::new(0.5, 0.25));
+    assert_relative_eq!(hit.normal, Vec2::Y);
+}
+
+#[test]
+fn casting_ray_agent() {
+    let mut app = make_app_with_map::<Floor>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, 1.0, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.0);
+    app.update();
+
+    let hit = cast::<Floor>(&mut app, layer, Vec2::new(0.5, 3.0), 0.0, Dir2::NEG_Y, 10.0).unwrap();
+    assert_eq!(hit.target, CollisionTarget::Agent(agent));
+    assert_relative_eq!(hit.distance, 1.8);
+    assert_relative_eq!(hit.point, Vec2::new(0.5, 1.2));
+    assert_relative_eq!(hit.normal, Vec2::Y);
+
+    let hit = cast::<Floor>(&mut app, layer, Vec2::new(0.8, 3.0), 0.2, Dir2::NEG_Y, 10.0).unwrap();
+    assert_eq!(hit.target, CollisionTarget::Agent(agent));
+    assert_relative_eq!(hit.distance, 2.0 - 0.07f32.sqrt());
+
+    let hit = app
+        .world_mut()
+        .run_system_once(move |query: CastQuery<Floor>| {
+            query.cast_ray(
+                layer,
+                Vec2::new(0.5, 3.0),
+                Dir2::NEG_Y,
+                10.0,
+                CollisionGroups::new(CollisionGroups::ALL, CollisionGroups::WALLS),
+            )
+        })
+        .unwrap()
+        .unwrap();
+    assert_eq!(hit.target, CollisionTarget::Wall);
+    assert_relative_eq!(hit.distance, 3.0);
+}
+
+#[test]
+fn colliding_fast_agent() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent1 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.0, 0.5, 0.0),
+            Velocity(Vec2::new(10.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent2 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(5.5, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position1, velocity1) = get_agent(&app, agent1);
+    assert_relative_eq!(position1, Vec2::new(5.1, 0.5));
+    assert_relative_eq!(velocity1, Vec2::new(0.0, 0.0));
+    let (position2, velocity2) = get_agent(&app, agent2);
+    assert_relative_eq!(position2, Vec2::new(5.5, 0.5));
+    assert_relative_eq!(velocity2, Vec2::new(0.0, 0.0));
+}
+
+#[test]
+fn colliding_fast_agent_diagonal() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent1 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, 0.5, 0.0),
+            Velocity(Vec2::new(20.0, 20.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent2 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(10.5, 10.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+    // Inside the bounding box of the path, but far from it.
+    let agent3 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(8.5, 2.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let contact = 10.5 - 0.4 * std::f32::consts::FRAC_1_SQRT_2;
+    let (position1, velocity1) = get_agent(&app, agent1);
+    assert_relative_eq!(position1, Vec2::splat(contact), epsilon = 1e-4);
+    assert_relative_eq!(velocity1, Vec2::new(0.0, 0.0));
+    let (position2, _) = get_agent(&app, agent2);
+    assert_relative_eq!(position2, Vec2::new(10.5, 10.5));
+    let (position3, _) = get_agent(&app, agent3);
+    assert_relative_eq!(position3, Vec2::new(8.5, 2.5));
+}
+
+#[test]
+fn colliding_fast_agents_crossing() {
+    let mut app = make_app();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent1 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.0, 0.5, 0.0),
+            Velocity(Vec2::new(10.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let agent2 = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(5.5, -4.5, 0.0),
+            Velocity(Vec2::new(0.0, 10.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    let collisions = update_get_collisions(&mut app);
+
+    let time_of_impact = (21.0 - 0.28f32.sqrt()) / 40.0;
+    let collision1 = collisions.iter().find(|c| c.agent == agent1).unwrap();
+    assert_eq!(collision1.other, CollisionTarget::Agent(agent2));
+    assert_relative_eq!(collision1.time_of_impact, time_of_impact, epsilon = 1e-5);
+    let collision2 = collisions.iter().find(|c| c.agent == agent2).unwrap();
+    assert_eq!(collision2.other, CollisionTarget::Agent(agent1));
+    assert_relative_eq!(collision2.time_of_impact, time_of_impact, epsilon = 1e-5);
+}
+
+#[test]
+fn colliding_fast_agent_thin_wall() {
+    let mut app = make_app_with_map::<Block>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(-4.5, 0.5, 0.0),
+            Velocity(Vec2::new(10.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(-0.2, 0.5), epsilon = 1e-5);
+    assert_relative_eq!(velocity, Vec2::new(0.0, 0.0));
+}
+
+#[test]
+fn colliding_agent_grid_wall() {
+    let mut app = make_app_with_map::<LayerGrid>();
+
+    let mut grid = TileGrid::new();
+    grid.fill_rect(IVec2::new(2, -5), IVec2::new(2, 5), true);
+
+    let layer = app.world_mut().spawn((Layer::default(), grid)).id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, 0.5, 0.0),
+            Velocity(Vec2::new(2.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(1.8, 0.5));
+    assert_relative_eq!(velocity, Vec2::new(0.0, 0.0));
+}
+
+#[test]
+fn tile_map_changed_agent_moved() {
+    let mut app = make_app_with_map::<LayerGrid>();
+
+    let layer = app
+        .world_mut()
+        .spawn((Layer::default(), TileGrid::new()))
+        .id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.4, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.0);
+    app.update();
+
+    app.world_mut()
+        .get_mut::<TileGrid>(layer)
+        .unwrap()
+        .set_solid(IVec2::new(0, 0), true);
+    app.world_mut().write_message(TileMapChanged {
+        layer,
+        tiles: vec![IVec2::new(0, 0)],
+    });
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position, _) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(-0.2, 0.5));
+}
+
+#[test]
+fn colliding_agent_cached_wall_changed() {
+    let mut app = make_app_with_map::<LayerGrid>();
+
+    let layer = app
+        .world_mut()
+        .spawn((Layer::default(), TileGrid::new(), TileCache::new()))
+        .id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, 0.5, 0.0),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.0);
+    app.update();
+
+    app.world_mut()
+        .get_mut::<TileGrid>(layer)
+        .unwrap()
+        .set_solid(IVec2::new(2, 0), true);
+    app.world_mut().write_message(TileMapChanged {
+        layer,
+        tiles: vec![IVec2::new(2, 0)],
+    });
+    app.world_mut().get_mut::<Velocity>(agent).unwrap().0 = Vec2::new(2.0, 0.0);
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(position, Vec2::new(1.8, 0.5));
+    assert_relative_eq!(velocity, Vec2::new(0.0, 0.0));
+}
+
+/// A tile map with a floor below `y = 0`, and a slope rising to the east from `(2, 0)` to `(4, 2)`.
+#[derive(SystemParam)]
+struct Slope;
+
+impl TileMap for Slope {
+    fn is_solid(&self, _: Entity, tile: IVec2) -> bool {
+        tile.y < 0 || (tile.x >= 2 && tile.y <= tile.x - 2 && tile.y <= 1)
+    }
+
+    fn shape(&self, _: Entity, tile: IVec2) -> TileShape {
+        if tile.y >= 0 && tile.y == tile.x - 2 {
+            TileShape::DiagonalSouthEast
+        } else {
+            TileShape::Full
+        }
+    }
+}
+
+#[test]
+fn sliding_agent_diagonal_wall() {
+    let mut app = make_app_with_map::<Slope>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let agent = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, 0.25, 0.0),
+            Velocity(Vec2::new(2.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    // The agent touches the slope, then slides along it with the component of its velocity parallel to it.
+    let contact = 2.25 - 0.2 * std::f32::consts::SQRT_2;
+    let remaining = 1.0 - (contact - 0.5) / 2.0;
+    let (position, velocity) = get_agent(&app, agent);
+    assert_relative_eq!(
+        position,
+        Vec2::new(contact + remaining, 0.25 + remaining),
+        epsilon = 1e-5
+    );
+    assert_relative_eq!(velocity, Vec2::new(1.0, 1.0), epsilon = 1e-5);
+}
+
+#[test]
+fn casting_ray_diagonal_wall() {
+    let mut app = make_app_with_map::<Slope>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+
+    let hit = cast::<Slope>(
+        &mut app,
+        layer,
+        Vec2::new(2.75, 2.0),
+        0.0,
+        Dir2::NEG_Y,
+        10.0,
+    )
+    .unwrap();
+    assert_eq!(hit.target, CollisionTarget::Wall);
+    assert_relative_eq!(hit.distance, 1.25);
+    assert_relative_eq!(hit.point, Vec2::new(2.75, 0.75));
+    assert_relative_eq!(hit.normal, Vec2::new(-1.0, 1.0).normalize());
+}
+
+#[test]
+fn casting_ray_inside_diagonal_wall() {
+    let mut app = make_app_with_map::<Slope>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+
+    let hit = cast::<Slope>(&mut app, layer, Vec2::new(2.9, 0.1), 0.0, Dir2::Y, 10.0);
+    assert_eq!(
+        hit,
+        Some(CastHit {
+            target: CollisionTarget::Wall,
+            distance: 0.0,
+            point: Vec2::new(2.9, 0.1),
+            normal: Vec2::NEG_Y,
+        })
+    );
+
+    let hit = cast::<Slope>(&mut app, layer, Vec2::new(2.1, 0.9), 0.0, Dir2::Y, 10.0);
+    assert_eq!(hit, None);
+}
+
+/// A tile map with a fence along the south side of the tiles from `(0, 0)` to `(2, 0)`.
+#[derive(SystemParam)]
+struct Fence;
+
+impl TileMap for Fence {
+    const HAS_EDGES: bool = true;
+
+    fn is_solid(&self, _: Entity, _: IVec2) -> bool {
+        false
+    }
+
+    fn is_edge_solid(&self, _: Entity, tile: IVec2, side: CompassQuadrant) -> bool {
+        side == CompassQuadrant::South && tile.y == 0 && (0..3).contains(&tile.x)
+    }
+}
+
+#[test]
+fn colliding_agent_thin_wall() {
+    let mut app = make_app_with_map::<Fence>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let blocked = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(1.5, 1.5, 0.0),
+            Velocity(Vec2::new(0.0, -2.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let below = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, -1.5, 0.0),
+            Velocity(Vec2::new(0.0, 2.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let past_end = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(3.5, 1.5, 0.0),
+            Velocity(Vec2::new(0.0, -2.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position, velocity) = get_agent(&app, blocked);
+    assert_relative_eq!(position, Vec2::new(1.5, 0.2));
+    assert_relative_eq!(velocity, Vec2::ZERO);
+
+    let (position, velocity) = get_agent(&app, below);
+    assert_relative_eq!(position, Vec2::new(0.5, -0.2));
+    assert_relative_eq!(velocity, Vec2::ZERO);
+
+    let (position, _) = get_agent(&app, past_end);
+    assert_relative_eq!(position, Vec2::new(3.5, -0.5));
+}
+
+#[derive(SystemParam)]
+struct Ledge;
+
+impl TileMap for Ledge {
+    const HAS_EDGES: bool = true;
+
+    fn is_solid(&self, _: Entity, _: IVec2) -> bool {
+        false
+    }
+
+    fn is_edge_blocked(&self, _: Entity, tile: IVec2, side: CompassQuadrant) -> bool {
+        side == CompassQuadrant::North && tile.y == -1 && (0..3).contains(&tile.x)
+    }
+}
+
+#[test]
+fn colliding_agent_one_way_edge() {
+    let mut app = make_app_with_map::<Ledge>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let dropping = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(1.5, 1.5, 0.0),
+            Velocity(Vec2::new(0.0, -2.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let climbing = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, -1.5, 0.0),
+            Velocity(Vec2::new(0.0, 2.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position, _) = get_agent(&app, dropping);
+    assert_relative_eq!(position, Vec2::new(1.5, -0.5));
+
+    let (position, velocity) = get_agent(&app, climbing);
+    assert_relative_eq!(position, Vec2::new(0.5, -0.2));
+    assert_relative_eq!(velocity, Vec2::ZERO);
+}
+
+#[derive(SystemParam)]
+struct Mud;
+
+impl TileMap for Mud {
+    fn is_solid(&self, _: Entity, tile: IVec2) -> bool {
+        tile.y < 0
+    }
+
+    fn speed_multiplier(&self, _: Entity, tile: IVec2) -> f32 {
+        if tile.x >= 2 { 0.5 } else { 1.0 }
+    }
+}
+
+#[test]
+fn moving_agent_speed_multiplier() {
+    let mut app = make_app_with_map::<Mud>();
+
+    let layer = app.world_mut().spawn(Layer::default()).id();
+    let slowed = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(2.5, 2.5, 0.0),
+            Velocity(Vec2::new(2.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let unslowed = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(0.5, 4.5, 0.0),
+            Velocity(Vec2::new(1.0, 0.0)),
+            ChildOf(layer),
+        ))
+        .id();
+    let sliding = app
+        .world_mut()
+        .spawn((
+            Agent::new(0.2),
+            Transform::from_xyz(4.5, 0.5, 0.0),
+            Velocity(Vec2::new(2.0, -2.0)),
+            ChildOf(layer),
+        ))
+        .id();
+
+    advance_time(&mut app, 1.5);
+    app.update();
+    advance_time(&mut app, 0.5);
+    app.update();
+
+    let (position, velocity) = get_agent(&app, slowed);
+    assert_relative_eq!(position, Vec2::new(3.5, 2.5));
+    assert_relative_eq!(velocity, Vec2::new(2.0, 0.0));
+
+    let (position, _) = get_agent(&app, unslowed);
+    assert_relative_eq!(position, Vec2::new(1.5, 4.5));
+
+    let (position, velocity) = get_agent(&app, sliding);
+    assert_relative_eq!(position, Vec2::new(5.5, 0.2));
+    assert_relative_eq!(velocity, Vec2::new(2.0, 0.0));
+}
+
+fn make_app() -> App {
+    make_app_with_map::<()>()
+}
+
+fn make_app_with_map<T>() -> App
+where
+    T: TileMap + 'static,
+    for<'w, 's> bevy::ecs::system::SystemParamItem<'w, 's, T>: TileMap,
+{
+    let mut app = App::new();
+    app.add_plugins((TransformPlugin, TimePlugin, JostlePlugin::<T>::default()));
+    app.finish();
+    app.cleanup();
+
+    app.insert_resource(Time::<Fixed>::from_duration(Duration::from_secs(1)));
+    app.insert_resource(Time::<Virtual>::from_max_delta(Duration::MAX));
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+
+    // Initialize time resource
+    app.world_mut()
+        .resource_mut::<Time<Real>>()
+        .update_with_duration(Duration::ZERO);
+
+    app
+}
+
+fn advance_time(app: &mut App, secs: f32) {
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+        secs,
+    )));
+}
+
+fn get_agent(app: &App, id: Entity) -> (Vec2, Vec2) {
+    (
+        app.world().get::<Transform>(id).unwrap().translation.xy(),
+        app.world().get::<Velocity>(id).unwrap().0,
+    )
+}
+
+fn cast<T>(
+    app: &mut App,
+    layer: Entity,
+    origin: Vec2,
+    radius: f32,
+    direction: Dir2,
+    max_distance: f32,
+) -> Option<CastHit>
+where
+    T: TileMap + 'static,
+    for<'w, 's> bevy::ecs::system::SystemParamItem<'w, 's, T>: TileMap,
+{
+    app.world_mut()
+        .run_system_once(move |query: CastQuery<T>| {
+            query.cast_circle(
+                layer,
+                origin,
+                radius,
+                direction,
+                max_distance,
+                CollisionGroups::default(),
+            )
+        })
+        .unwrap()
+}
+
+fn update_get_sensor_messages(app: &mut App) -> (Vec<SensorEntered>, Vec<SensorExited>) {
+    let mut entered_cursor = app
+        .world()
+        .resource::<Messages<SensorEntered>>()
+        .get_cursor_current();
+    let mut exited_cursor = app
+        .world()
+        .resource::<Messages<SensorExited>>()
+        .get_cursor_current();
+
+    app.update();
+
+    (
+        entered_cursor
+            .read(app.world().resource::<Messages<SensorEntered>>())
+            .cloned()
+            .collect(),
+        exited_cursor
+            .read(app.world().resource::<Messages<SensorExited>>())
+            .cloned()
+            .collect(),
+    )
+}
+
+fn update_get_collisions(app: &mut App) -> Vec<AgentCollided> {
+    let mut cursor = app
+        .world()
+        .resource::<Messages<AgentCollided>>()
+        .get_cursor_current();
+
+    app.update();
+
+    cursor
+        .read(app.world().resource::<Messages<AgentCollided>>())
+        .cloned()
+        .collect()
 }